@@ -0,0 +1,272 @@
+use crate::{
+    dry_run::{DryRun, PlannedEntry},
+    error::{Cause, Error, Operation},
+    filesystem::{Manifest, PROJECT_FILE, SRC},
+    process_instructions, ExportOptions,
+};
+use rbx_dom_weak::{types::Variant, Instance, WeakDom};
+use similar::TextDiff;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt, fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+/// How a file in the project differs from what the place would export.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChangeKind {
+    /// The place has it, the project doesn't.
+    Added,
+    /// The project has it, the place doesn't.
+    Removed,
+    Modified,
+}
+
+impl fmt::Display for ChangeKind {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str(match self {
+            ChangeKind::Added => "added",
+            ChangeKind::Removed => "removed",
+            ChangeKind::Modified => "modified",
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileChange {
+    /// Relative to the project folder.
+    pub path: PathBuf,
+    pub kind: ChangeKind,
+    /// A unified diff from the project to the place, for scripts.
+    pub diff: Option<String>,
+}
+
+/// Every difference between a project on disk and what the place would export.
+#[derive(Clone, Debug, Default)]
+pub struct Drift {
+    pub changes: Vec<FileChange>,
+}
+
+impl Drift {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    fn count(&self, kind: ChangeKind) -> usize {
+        self.changes
+            .iter()
+            .filter(|change| change.kind == kind)
+            .count()
+    }
+}
+
+impl fmt::Display for Drift {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        for change in &self.changes {
+            writeln!(formatter, "{} {}", change.kind, change.path.display())?;
+
+            if let Some(diff) = &change.diff {
+                writeln!(formatter, "{}", diff)?;
+            }
+        }
+
+        write!(
+            formatter,
+            "{} files differ: {} added, {} removed, {} modified",
+            self.changes.len(),
+            self.count(ChangeKind::Added),
+            self.count(ChangeKind::Removed),
+            self.count(ChangeKind::Modified),
+        )
+    }
+}
+
+fn is_script(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|extension| extension.to_str()),
+        Some("lua") | Some("luau")
+    )
+}
+
+fn script_diff(path: &Path, project: &[u8], place: &[u8]) -> String {
+    let project = String::from_utf8_lossy(project);
+    let place = String::from_utf8_lossy(place);
+    let path = path.to_string_lossy().replace("\\", "/");
+
+    TextDiff::from_lines(project.as_ref(), place.as_ref())
+        .unified_diff()
+        .context_radius(3)
+        .header(&format!("project/{}", path), &format!("place/{}", path))
+        .to_string()
+}
+
+fn decode_model(path: &Path, contents: &[u8]) -> Option<WeakDom> {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("rbxmx") => rbx_xml::from_reader_default(contents).ok(),
+        Some("rbxm") => rbx_binary::from_reader(contents).ok(),
+        _ => None,
+    }
+}
+
+fn properties_match(lhs: &Instance, rhs: &Instance) -> bool {
+    lhs.properties.len() == rhs.properties.len()
+        && lhs.properties.iter().all(|(name, value)| {
+            match (value, rhs.properties.get(name)) {
+                // Referents are made up fresh every time a model is written
+                (Variant::Ref(lhs), Some(Variant::Ref(rhs))) => lhs.is_none() == rhs.is_none(),
+                (lhs, Some(rhs)) => lhs == rhs,
+                (_, None) => false,
+            }
+        })
+}
+
+fn instances_match(lhs_dom: &WeakDom, lhs: &Instance, rhs_dom: &WeakDom, rhs: &Instance) -> bool {
+    lhs.name == rhs.name
+        && lhs.class == rhs.class
+        && properties_match(lhs, rhs)
+        && lhs.children().len() == rhs.children().len()
+        && lhs.children().iter().zip(rhs.children()).all(|(lhs, rhs)| {
+            match (lhs_dom.get_by_ref(*lhs), rhs_dom.get_by_ref(*rhs)) {
+                (Some(lhs), Some(rhs)) => instances_match(lhs_dom, lhs, rhs_dom, rhs),
+                _ => false,
+            }
+        })
+}
+
+/// Whether two versions of a file say the same thing. Models are compared by what they
+/// contain, since the same instances are written with different referents every time.
+fn contents_match(path: &Path, project: &[u8], place: &[u8]) -> bool {
+    if project == place {
+        return true;
+    }
+
+    match (decode_model(path, project), decode_model(path, place)) {
+        (Some(project), Some(place)) => {
+            instances_match(&project, project.root(), &place, place.root())
+        }
+        _ => false,
+    }
+}
+
+fn collect_files(root: &Path, folder: &Path, files: &mut BTreeSet<PathBuf>) -> Result<(), Error> {
+    let full_path = root.join(folder);
+    let entries = match fs::read_dir(&full_path) {
+        Ok(entries) => entries,
+        Err(error) if error.kind() == ErrorKind::NotFound => return Ok(()),
+        Err(error) => return Err(Error::new(Operation::ReadFolder, full_path, error)),
+    };
+
+    for entry in entries {
+        let entry = entry.map_err(|error| Error::new(Operation::ReadFolder, &full_path, error))?;
+        let path = folder.join(entry.file_name());
+
+        if entry.path().is_dir() {
+            collect_files(root, &path, files)?;
+        } else {
+            files.insert(path);
+        }
+    }
+
+    Ok(())
+}
+
+/// The files of the project that an export would own. With a manifest, that's exactly what
+/// the last export wrote, so files added by hand don't show up as removed. Without one, it's
+/// everything in the project, but a manifest that can't be read is an error.
+fn project_files(project_root: &Path) -> Result<BTreeSet<PathBuf>, Error> {
+    match Manifest::read(project_root) {
+        Ok(manifest) => {
+            return Ok(manifest
+                .files
+                .iter()
+                .map(PathBuf::from)
+                .filter(|path| project_root.join(path).is_file())
+                .collect())
+        }
+
+        Err(error) => match error.cause() {
+            Cause::Io(io_error) if io_error.kind() == ErrorKind::NotFound => {}
+            _ => return Err(error),
+        },
+    }
+
+    let mut files = BTreeSet::new();
+    collect_files(project_root, Path::new(SRC), &mut files)?;
+
+    if project_root.join(PROJECT_FILE).is_file() {
+        files.insert(PathBuf::from(PROJECT_FILE));
+    }
+
+    Ok(files)
+}
+
+/// Exports `tree` in memory and compares it with the Rojo project in `project_root`, such as
+/// to catch scripts that were edited in Studio but never brought back into the project.
+pub fn check_project(
+    tree: &WeakDom,
+    project_root: &Path,
+    options: &ExportOptions,
+) -> Result<Drift, Error> {
+    let mut dry_run = DryRun::new();
+    process_instructions(tree, &mut dry_run, options)?;
+
+    let planned: BTreeMap<&Path, &[u8]> = dry_run
+        .entries()
+        .iter()
+        .filter_map(|(path, entry)| match entry {
+            PlannedEntry::File(contents) => Some((path.as_path(), contents.as_slice())),
+            PlannedEntry::Folder => None,
+        })
+        .collect();
+
+    let existing = project_files(project_root)?;
+    let mut drift = Drift::default();
+
+    for (path, place) in &planned {
+        if !existing.contains(*path) {
+            drift.changes.push(FileChange {
+                path: path.to_path_buf(),
+                kind: ChangeKind::Added,
+                diff: is_script(path).then(|| script_diff(path, &[], place)),
+            });
+            continue;
+        }
+
+        let full_path = project_root.join(path);
+        let project = fs::read(&full_path)
+            .map_err(|error| Error::new(Operation::ReadFile, full_path, error))?;
+
+        if !contents_match(path, &project, place) {
+            drift.changes.push(FileChange {
+                path: path.to_path_buf(),
+                kind: ChangeKind::Modified,
+                diff: is_script(path).then(|| script_diff(path, &project, place)),
+            });
+        }
+    }
+
+    for path in &existing {
+        if planned.contains_key(path.as_path()) {
+            continue;
+        }
+
+        let diff = if is_script(path) {
+            let full_path = project_root.join(path);
+            let project = fs::read(&full_path)
+                .map_err(|error| Error::new(Operation::ReadFile, full_path, error))?;
+            Some(script_diff(path, &project, &[]))
+        } else {
+            None
+        };
+
+        drift.changes.push(FileChange {
+            path: path.clone(),
+            kind: ChangeKind::Removed,
+            diff,
+        });
+    }
+
+    drift.changes.sort_by(|lhs, rhs| lhs.path.cmp(&rhs.path));
+    Ok(drift)
+}
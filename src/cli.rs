@@ -1,20 +1,311 @@
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use log::info;
-use rbxlx_to_rojo::{filesystem::FileSystem, process_instructions, ExportMode};
+use rbx_dom_weak::WeakDom;
+use rbxlx_to_rojo::{
+    check::check_project,
+    config::Config,
+    dry_run::DryRun,
+    error::Error as ExportError,
+    filesystem::FileSystem,
+    filter::Filters,
+    process_instructions,
+    verify::{build_project_with_rojo, find_rojo, verify_project, verify_rebuilt},
+    ExportMode, ExportOptions, ModelFormat, ScriptExtension,
+};
 use std::{
     borrow::Cow,
     fmt, fs,
-    io::{self, BufReader, Write},
-    path::PathBuf,
+    io::{self, BufReader, IsTerminal, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
     sync::{Arc, RwLock},
 };
 
+/// Converts Roblox place and model files into Rojo projects.
+#[derive(Debug, Parser)]
+#[command(version, args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[command(flatten)]
+    export: ExportArgs,
+
+    /// How much to log, both to the terminal and to rbxlx-to-rojo.log
+    #[arg(long, value_enum, global = true, default_value_t = LogLevel::Info)]
+    log_level: LogLevel,
+
+    /// Only print errors to the terminal. The log file is still written in full.
+    #[arg(short, long, global = true)]
+    quiet: bool,
+
+    /// Exit straight away instead of waiting for Enter to be pressed
+    #[arg(long, global = true)]
+    no_pause: bool,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Convert a place or model file into a Rojo project. This is the default.
+    Export(ExportArgs),
+
+    /// Check that a Rojo project is up to date with a place file. Prints what differs and
+    /// exits with a non-zero code if anything does.
+    Check(CheckArgs),
+
+    /// Rebuild the place from a Rojo project and compare it with the place file, instance by
+    /// instance. Prints what was lost or changed and exits with a non-zero code if anything was.
+    Verify(CheckArgs),
+}
+
+#[derive(Debug, Args)]
+struct ExportArgs {
+    /// The place or model file to convert (.rbxl, .rbxlx, .rbxm or .rbxmx)
+    #[arg(short, long, value_name = "FILE")]
+    input: Option<PathBuf>,
+
+    /// The folder to create the Rojo project in
+    #[arg(short, long, value_name = "FOLDER")]
+    output: Option<PathBuf>,
+
+    // Kept so files can still be dropped onto the executable
+    #[arg(hide = true, conflicts_with = "input")]
+    input_positional: Option<PathBuf>,
+
+    #[arg(hide = true, conflicts_with = "output")]
+    output_positional: Option<PathBuf>,
+
+    #[command(flatten)]
+    conversion: ConversionArgs,
+
+    /// Write into the project folder even if it already has files in it
+    #[arg(short, long)]
+    force: bool,
+
+    /// Update a project from an earlier export in place, only touching files that changed.
+    /// Files that weren't written by rbxlx-to-rojo are left alone.
+    #[arg(short, long, conflicts_with_all = ["force", "dry_run"])]
+    update: bool,
+
+    /// Pick any missing input or output path with a file dialog
+    #[arg(long)]
+    dialog: bool,
+
+    /// Print the files and project that would be written, without writing anything
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Write what happened to every instance to rbxlx-to-rojo.report.json, next to the log
+    #[arg(long)]
+    report: bool,
+}
+
+#[derive(Debug, Args)]
+struct CheckArgs {
+    /// The place or model file to compare (.rbxl, .rbxlx, .rbxm or .rbxmx)
+    #[arg(value_name = "FILE")]
+    input: PathBuf,
+
+    /// The Rojo project folder, the one with default.project.json in it
+    #[arg(value_name = "PROJECT")]
+    project: PathBuf,
+
+    #[command(flatten)]
+    conversion: ConversionArgs,
+}
+
+impl CheckArgs {
+    /// The project is usually exported next to the configuration, or into a folder beside it.
+    fn options(&self) -> Result<ExportOptions, Problem> {
+        let mut folders = vec![self.project.as_path()];
+        folders.extend(self.project.parent());
+        self.conversion.options(&self.input, &folders)
+    }
+}
+
+/// How instances are turned into files. These have to match between exporting a project
+/// and checking it. Anything given here overrides rbxlx-to-rojo.toml.
+#[derive(Debug, Args)]
+struct ConversionArgs {
+    /// Read the configuration from this file instead of looking for rbxlx-to-rojo.toml in the
+    /// output folder and the current folder
+    #[arg(long, value_name = "FILE")]
+    config: Option<PathBuf>,
+
+    /// The name of the project in default.project.json, and of its folder. Defaults to the
+    /// name of the place file.
+    #[arg(long)]
+    name: Option<String>,
+
+    /// What to export [default: full]
+    #[arg(long, value_enum)]
+    mode: Option<Mode>,
+
+    #[arg(long, hide = true, conflicts_with = "mode")]
+    scripts_only: bool,
+
+    /// Write scripts as .lua instead of .luau
+    #[arg(long)]
+    lua: bool,
+
+    /// Write models as binary .rbxm instead of .rbxmx
+    #[arg(long)]
+    binary_models: bool,
+
+    /// Write models with at most this many instances as .model.json
+    #[arg(long, value_name = "COUNT")]
+    model_json: Option<usize>,
+
+    /// Write containers holding scripts, like tools and models, as folders with their class and
+    /// properties in init.meta.json instead of as models
+    #[arg(long)]
+    containers_as_folders: bool,
+
+    /// Only export the instance at this DataModel path, e.g. ServerScriptService.Combat, and
+    /// what's inside it. Can be given more than once.
+    #[arg(long = "subtree", value_name = "PATH")]
+    subtrees: Vec<String>,
+
+    /// Read include and exclude rules from this TOML file
+    #[arg(long, value_name = "FILE")]
+    filters: Option<PathBuf>,
+
+    /// Only export instances matching this DataModel path glob, e.g. Workspace.Map.**, and
+    /// what leads to them. Can be given more than once.
+    #[arg(long, value_name = "GLOB")]
+    include: Vec<String>,
+
+    /// Only export instances of this class, and what leads to them
+    #[arg(long, value_name = "CLASS")]
+    include_class: Vec<String>,
+
+    /// Leave out instances matching this DataModel path glob, e.g. **/ThirdParty/**
+    #[arg(long, value_name = "GLOB")]
+    exclude: Vec<String>,
+
+    /// Leave out instances of this class, e.g. Camera
+    #[arg(long, value_name = "CLASS")]
+    exclude_class: Vec<String>,
+}
+
+impl ConversionArgs {
+    fn filters(&self) -> Result<Filters, Problem> {
+        let mut filters = match &self.filters {
+            Some(path) => Filters::from_file(path).map_err(Problem::ConfigError)?,
+            None => Filters::default(),
+        };
+
+        filters.extend(Filters {
+            include: self.include.clone(),
+            include_classes: self.include_class.clone(),
+            exclude: self.exclude.clone(),
+            exclude_classes: self.exclude_class.clone(),
+        });
+
+        Ok(filters)
+    }
+
+    /// The configuration given with `--config`, or else the first rbxlx-to-rojo.toml found
+    /// in `folders` or the current folder.
+    fn config(&self, folders: &[&Path]) -> Result<Config, Problem> {
+        if let Some(path) = &self.config {
+            info!("Using the configuration in {}", path.display());
+            return Config::from_file(path).map_err(Problem::ConfigError);
+        }
+
+        let current_dir = std::env::current_dir().ok();
+        let folders = folders.iter().copied().chain(current_dir.as_deref());
+
+        match Config::find(folders).map_err(Problem::ConfigError)? {
+            Some((path, config)) => {
+                info!("Using the configuration in {}", path.display());
+                Ok(config)
+            }
+            None => Ok(Config::default()),
+        }
+    }
+
+    fn options(&self, input: &Path, folders: &[&Path]) -> Result<ExportOptions, Problem> {
+        let mut options = self.config(folders)?.options();
+
+        if self.name.is_some() {
+            options.project.name = self.name.clone();
+        } else if options.project.name.is_none() {
+            options.project.name = input
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned());
+        }
+
+        if self.scripts_only || self.mode == Some(Mode::ScriptsOnly) {
+            options.mode = ExportMode::ScriptsOnly;
+        } else if self.mode == Some(Mode::Full) {
+            options.mode = ExportMode::Full;
+        }
+
+        if self.lua {
+            options.script_extension = ScriptExtension::Lua;
+        }
+
+        if self.binary_models {
+            options.model_format = ModelFormat::Binary;
+        }
+
+        if self.model_json.is_some() {
+            options.model_json_threshold = self.model_json;
+        }
+
+        if self.containers_as_folders {
+            options.containers_as_folders = true;
+        }
+
+        options.subtrees = self.subtrees.clone();
+        options.filters.extend(self.filters()?);
+        Ok(options)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum Mode {
+    /// Scripts, models and everything else in the place
+    Full,
+    /// Only scripts and the folders that lead to them
+    ScriptsOnly,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl From<LogLevel> for log::LevelFilter {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Error => log::LevelFilter::Error,
+            LogLevel::Warn => log::LevelFilter::Warn,
+            LogLevel::Info => log::LevelFilter::Info,
+            LogLevel::Debug => log::LevelFilter::Debug,
+            LogLevel::Trace => log::LevelFilter::Trace,
+        }
+    }
+}
+
 #[derive(Debug)]
 enum Problem {
     BinaryDecodeError(rbx_binary::DecodeError),
+    Drift(usize),
+    Mismatches(usize),
+    ConfigError(ExportError),
+    ExportError(ExportError),
     InvalidFile,
     IoError(&'static str, io::Error),
+    MissingPath(&'static str),
     NFDCancel,
     NFDError(String),
+    OutputNotEmpty(PathBuf),
     XMLDecodeError(rbx_xml::DecodeError),
 }
 
@@ -27,6 +318,28 @@ impl fmt::Display for Problem {
                 error,
             ),
 
+            Problem::Drift(count) => write!(
+                formatter,
+                "The project is out of date with the place file, {} files differ.",
+                count,
+            ),
+
+            Problem::Mismatches(count) => write!(
+                formatter,
+                "The project doesn't round trip to the place file, {} instances differ.",
+                count,
+            ),
+
+            Problem::ConfigError(error) => {
+                write!(formatter, "While reading the configuration, {}", error)
+            }
+
+            Problem::ExportError(error) => write!(
+                formatter,
+                "While writing the Rojo project, {}",
+                error,
+            ),
+
             Problem::InvalidFile => {
                 write!(formatter, "The file provided does not have a recognized file extension")
             }
@@ -35,6 +348,12 @@ impl fmt::Display for Problem {
                 write!(formatter, "While attempting to {}, {}", doing_what, error)
             }
 
+            Problem::MissingPath(argument) => write!(
+                formatter,
+                "No {} was given. Pass it on the command line, or use --dialog to pick it.",
+                argument,
+            ),
+
             Problem::NFDCancel => write!(formatter, "Didn't choose a file."),
 
             Problem::NFDError(error) => write!(
@@ -43,6 +362,12 @@ impl fmt::Display for Problem {
                 error,
             ),
 
+            Problem::OutputNotEmpty(path) => write!(
+                formatter,
+                "{} already has files in it. Use --update to update it, or --force to write into it anyway.",
+                path.display(),
+            ),
+
             Problem::XMLDecodeError(error) => write!(
                 formatter,
                 "While attempting to decode the place file, at {} rbx_xml didn't know what to do",
@@ -55,17 +380,20 @@ impl fmt::Display for Problem {
 struct WrappedLogger {
     log: env_logger::Logger,
     log_file: Arc<RwLock<Option<fs::File>>>,
+    file_level: log::LevelFilter,
 }
 
 impl log::Log for WrappedLogger {
     fn enabled(&self, metadata: &log::Metadata) -> bool {
-        self.log.enabled(metadata)
+        self.log.enabled(metadata) || metadata.level() <= self.file_level
     }
 
     fn log(&self, record: &log::Record) {
-        if self.enabled(record.metadata()) {
+        if self.log.enabled(record.metadata()) {
             self.log.log(record);
+        }
 
+        if record.level() <= self.file_level {
             if let Some(ref mut log_file) = &mut *self.log_file.write().unwrap() {
                 log_file
                     .write(format!("{}\r\n", record.args()).as_bytes())
@@ -77,78 +405,116 @@ impl log::Log for WrappedLogger {
     fn flush(&self) {}
 }
 
-fn routine() -> Result<(), Problem> {
-    let env_logger = env_logger::Builder::new()
-        .filter_level(log::LevelFilter::Info)
-        .build();
-
-    let log_file = Arc::new(RwLock::new(None));
-    let logger = WrappedLogger {
-        log: env_logger,
-        log_file: Arc::clone(&log_file),
-    };
-
-    log::set_boxed_logger(Box::new(logger)).unwrap();
-    log::set_max_level(log::LevelFilter::Info);
+fn choose_path(
+    given: Option<PathBuf>,
+    argument: &'static str,
+    use_dialog: bool,
+    dialog: impl FnOnce() -> nfd::Result<nfd::Response>,
+) -> Result<PathBuf, Problem> {
+    if let Some(path) = given {
+        return Ok(path);
+    }
 
-    info!("rbxlx-to-rojo {}", env!("CARGO_PKG_VERSION"));
+    if !use_dialog {
+        return Err(Problem::MissingPath(argument));
+    }
 
-    info!("Select a place file.");
-    let mut args: Vec<String> = std::env::args().skip(1).collect();
-    let scripts_only = if let Some(pos) = args.iter().position(|a| a == "--scripts-only") {
-        args.remove(pos);
-        true
-    } else {
-        false
-    };
+    match dialog().map_err(|error| Problem::NFDError(error.to_string()))? {
+        nfd::Response::Okay(path) => Ok(PathBuf::from(path)),
+        nfd::Response::Cancel => Err(Problem::NFDCancel),
+        _ => unreachable!(),
+    }
+}
 
-    let file_path = PathBuf::from(match args.get(0) {
-        Some(text) => text.clone(),
-        None => match nfd::open_file_dialog(Some("rbxl,rbxm,rbxlx,rbxmx"), None)
-            .map_err(|error| Problem::NFDError(error.to_string()))?
-        {
-            nfd::Response::Okay(path) => path,
-            nfd::Response::Cancel => Err(Problem::NFDCancel)?,
-            _ => unreachable!(),
-        },
-    });
+fn has_files(path: &Path) -> bool {
+    fs::read_dir(path)
+        .map(|mut entries| entries.next().is_some())
+        .unwrap_or(false)
+}
 
+/// Decodes a place or model file. XML files are read with `property_behavior`; binary files
+/// always go through reflection.
+fn decode_place(
+    file_path: &Path,
+    property_behavior: rbx_xml::DecodePropertyBehavior,
+) -> Result<WeakDom, Problem> {
     info!("Opening place file");
     let file_source = BufReader::new(
-        fs::File::open(&file_path)
+        fs::File::open(file_path)
             .map_err(|error| Problem::IoError("read the place file", error))?,
     );
     info!("Decoding place file, this is the longest part...");
 
-    let tree = match file_path
+    match file_path
         .extension()
         .map(|extension| extension.to_string_lossy())
     {
         Some(Cow::Borrowed("rbxmx")) | Some(Cow::Borrowed("rbxlx")) => {
-            // Use NoReflection to handle unknown/new property types that rbx_xml doesn't support yet
-            let options = rbx_xml::DecodeOptions::new()
-                .property_behavior(rbx_xml::DecodePropertyBehavior::NoReflection);
+            let options = rbx_xml::DecodeOptions::new().property_behavior(property_behavior);
             rbx_xml::from_reader(file_source, options).map_err(Problem::XMLDecodeError)
         }
         Some(Cow::Borrowed("rbxm")) | Some(Cow::Borrowed("rbxl")) => {
             rbx_binary::from_reader(file_source).map_err(Problem::BinaryDecodeError)
         }
         _ => Err(Problem::InvalidFile),
-    }?;
-
-    info!("Select the path to put your Rojo project in.");
-    let root = PathBuf::from(match args.get(1) {
-        Some(text) => text.clone(),
-        None => match nfd::open_pick_folder(Some(&file_path.parent().unwrap().to_string_lossy()))
-            .map_err(|error| Problem::NFDError(error.to_string()))?
-        {
-            nfd::Response::Okay(path) => path,
-            nfd::Response::Cancel => Err(Problem::NFDCancel)?,
-            _ => unreachable!(),
-        },
-    });
-
-    let mut filesystem = FileSystem::from_root(root.join(file_path.file_stem().unwrap()).into());
+    }
+}
+
+fn export(
+    args: &ExportArgs,
+    use_dialog: bool,
+    log_file: &RwLock<Option<fs::File>>,
+) -> Result<(), Problem> {
+    if use_dialog && args.input.is_none() && args.input_positional.is_none() {
+        info!("Select a place file.");
+    }
+    let file_path = choose_path(
+        args.input.clone().or_else(|| args.input_positional.clone()),
+        "input file",
+        use_dialog,
+        || nfd::open_file_dialog(Some("rbxl,rbxm,rbxlx,rbxmx"), None),
+    )?;
+
+    // Use NoReflection to handle unknown/new property types that rbx_xml doesn't support yet
+    let tree = decode_place(&file_path, rbx_xml::DecodePropertyBehavior::NoReflection)?;
+    let given_output = args
+        .output
+        .clone()
+        .or_else(|| args.output_positional.clone());
+
+    if args.dry_run {
+        let folders: Vec<&Path> = given_output.iter().map(PathBuf::as_path).collect();
+        let options = args.conversion.options(&file_path, &folders)?;
+
+        info!("Planning the project, nothing will be written...");
+        let mut dry_run = DryRun::new();
+        let report =
+            process_instructions(&tree, &mut dry_run, &options).map_err(Problem::ExportError)?;
+        println!("{}", dry_run.summary(&report));
+        return Ok(());
+    }
+
+    if use_dialog && args.output.is_none() && args.output_positional.is_none() {
+        info!("Select the path to put your Rojo project in.");
+    }
+    let root = choose_path(given_output, "output folder", use_dialog, || {
+        nfd::open_pick_folder(Some(
+            &file_path
+                .parent()
+                .unwrap_or(Path::new(""))
+                .to_string_lossy(),
+        ))
+    })?;
+
+    let options = args.conversion.options(&file_path, &[&root])?;
+    let project_root = root.join(options.project.name.as_deref().unwrap_or("project"));
+    let mut filesystem = if args.update {
+        FileSystem::update_root(project_root).map_err(Problem::ExportError)?
+    } else if !args.force && has_files(&project_root) {
+        return Err(Problem::OutputNotEmpty(project_root));
+    } else {
+        FileSystem::from_root(project_root)
+    };
 
     log_file.write().unwrap().replace(
         fs::File::create(root.join("rbxlx-to-rojo.log"))
@@ -156,24 +522,128 @@ fn routine() -> Result<(), Problem> {
     );
 
     info!("Starting processing, please wait a bit...");
-    let mode = if scripts_only { ExportMode::ScriptsOnly } else { ExportMode::Full };
-    process_instructions(&tree, &mut filesystem, mode);
+    let report =
+        process_instructions(&tree, &mut filesystem, &options).map_err(Problem::ExportError)?;
+
+    if args.report {
+        let report_json = serde_json::to_string_pretty(&report).expect("couldn't serialize report");
+        fs::write(root.join("rbxlx-to-rojo.report.json"), report_json)
+            .map_err(|error| Problem::IoError("write the report", error))?;
+    }
     info!("Done! Check rbxlx-to-rojo.log for a full log.");
     Ok(())
 }
 
+fn check(args: &CheckArgs) -> Result<(), Problem> {
+    let tree = decode_place(&args.input, rbx_xml::DecodePropertyBehavior::NoReflection)?;
+
+    info!("Comparing with {}...", args.project.display());
+    let drift =
+        check_project(&tree, &args.project, &args.options()?).map_err(Problem::ExportError)?;
+
+    if drift.is_empty() {
+        info!("The project is up to date.");
+        return Ok(());
+    }
+
+    println!("{}", drift);
+    Err(Problem::Drift(drift.changes.len()))
+}
+
+fn verify(args: &CheckArgs) -> Result<(), Problem> {
+    let tree = decode_place(&args.input, rbx_xml::DecodePropertyBehavior::NoReflection)?;
+    let options = args.options()?;
+
+    let verification = match find_rojo() {
+        Some(rojo) => {
+            info!(
+                "Building {} with {}...",
+                args.project.display(),
+                rojo.display()
+            );
+            let rebuilt =
+                build_project_with_rojo(&args.project, &rojo).map_err(Problem::ExportError)?;
+            verify_rebuilt(&tree, &rebuilt, &options).map_err(Problem::ExportError)?
+        }
+
+        None => {
+            info!(
+                "Rojo isn't installed, rebuilding {} with its rules...",
+                args.project.display()
+            );
+            verify_project(&tree, &args.project, &options).map_err(Problem::ExportError)?
+        }
+    };
+
+    if verification.is_empty() {
+        info!("The project rebuilds into the same place.");
+        return Ok(());
+    }
+
+    println!("{}", verification);
+    Err(Problem::Mismatches(verification.mismatches.len()))
+}
+
+fn routine(cli: &Cli, use_dialog: bool) -> Result<(), Problem> {
+    let console_level = if cli.quiet {
+        log::LevelFilter::Error
+    } else {
+        cli.log_level.into()
+    };
+
+    let env_logger = env_logger::Builder::new()
+        .filter_level(console_level)
+        .build();
+
+    let log_file = Arc::new(RwLock::new(None));
+    let logger = WrappedLogger {
+        log: env_logger,
+        log_file: Arc::clone(&log_file),
+        file_level: cli.log_level.into(),
+    };
+
+    log::set_boxed_logger(Box::new(logger)).unwrap();
+    log::set_max_level(console_level.max(cli.log_level.into()));
+
+    info!("rbxlx-to-rojo {}", env!("CARGO_PKG_VERSION"));
+
+    match &cli.command {
+        Some(Command::Export(args)) => export(args, use_dialog, &log_file),
+        Some(Command::Check(args)) => check(args),
+        Some(Command::Verify(args)) => verify(args),
+        None => export(&cli.export, use_dialog, &log_file),
+    }
+}
+
 fn wait_for_keypress() {
     println!("\nPress Enter to exit...");
     let mut input = String::new();
     io::stdin().read_line(&mut input).ok();
 }
 
-fn main() {
-    if let Err(error) = routine() {
+fn main() -> ExitCode {
+    // Launching without any arguments is what double-clicking the executable does
+    let launched_bare = std::env::args_os().len() <= 1;
+    let cli = Cli::parse();
+
+    let has_terminal = io::stdin().is_terminal();
+    let use_dialog = cli.export.dialog
+        || matches!(&cli.command, Some(Command::Export(args)) if args.dialog)
+        || launched_bare
+        || !has_terminal;
+
+    let result = routine(&cli, use_dialog);
+    if let Err(error) = &result {
         eprintln!("An error occurred while using rbxlx-to-rojo.");
         eprintln!("{}", error);
+    }
+
+    if !cli.no_pause && has_terminal {
         wait_for_keypress();
-    } else {
-        wait_for_keypress();
+    }
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(_) => ExitCode::FAILURE,
     }
 }
@@ -0,0 +1,154 @@
+use crate::{
+    error::{Error, Operation},
+    filter::Filters,
+    structures::ProjectOptions,
+    ExportMode, ExportOptions, ModelFormat, ScriptExtension,
+};
+use serde::{de::DeserializeOwned, Deserialize};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// The file a configuration is read from, when it isn't given explicitly.
+pub const CONFIG_FILE: &str = "rbxlx-to-rojo.toml";
+
+pub(crate) fn read_toml<T: DeserializeOwned>(path: &Path) -> Result<T, Error> {
+    let contents =
+        fs::read_to_string(path).map_err(|error| Error::new(Operation::ReadFile, path, error))?;
+
+    toml::from_str(&contents).map_err(|error| {
+        Error::new(
+            Operation::ReadFile,
+            path,
+            io::Error::new(io::ErrorKind::InvalidData, error.to_string()),
+        )
+    })
+}
+
+/// Everything a conversion can be set up with, as written in `rbxlx-to-rojo.toml`, so the
+/// same conversion can be repeated without passing the same flags every time. Anything left
+/// out keeps its default.
+///
+/// ```toml
+/// mode = "scripts-only"
+/// script_extension = "lua"
+/// model_format = "binary"
+/// respected_services = ["ReplicatedStorage", "ServerScriptService"]
+///
+/// [project]
+/// name = "game"
+///
+/// [filters]
+/// exclude = ["**/ThirdParty/**"]
+/// ```
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub mode: Option<ExportMode>,
+    pub script_extension: Option<ScriptExtension>,
+    pub model_format: Option<ModelFormat>,
+    pub model_json_threshold: Option<usize>,
+    pub containers_as_folders: Option<bool>,
+    /// Replaces the services that are exported.
+    pub respected_services: Option<Vec<String>>,
+    /// Replaces the services that aren't added to the project tree.
+    pub non_tree_services: Option<Vec<String>>,
+    /// Replaces the services whose settings are kept in the project tree without exporting them.
+    pub settings_services: Option<Vec<String>>,
+    pub project: ProjectOptions,
+    pub filters: Filters,
+}
+
+impl Config {
+    pub fn from_file(path: &Path) -> Result<Self, Error> {
+        read_toml(path)
+    }
+
+    /// Reads the `rbxlx-to-rojo.toml` in the first of `folders` that has one, along with
+    /// where it was found.
+    pub fn find<'a>(
+        folders: impl IntoIterator<Item = &'a Path>,
+    ) -> Result<Option<(PathBuf, Self)>, Error> {
+        for folder in folders {
+            let path = folder.join(CONFIG_FILE);
+            if path.is_file() {
+                let config = Self::from_file(&path)?;
+                return Ok(Some((path, config)));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Sets everything this configuration has on `options`.
+    pub fn apply(&self, options: &mut ExportOptions) {
+        if let Some(mode) = self.mode {
+            options.mode = mode;
+        }
+
+        if let Some(script_extension) = self.script_extension {
+            options.script_extension = script_extension;
+        }
+
+        if let Some(model_format) = self.model_format {
+            options.model_format = model_format;
+        }
+
+        if self.model_json_threshold.is_some() {
+            options.model_json_threshold = self.model_json_threshold;
+        }
+
+        if let Some(containers_as_folders) = self.containers_as_folders {
+            options.containers_as_folders = containers_as_folders;
+        }
+
+        if let Some(services) = &self.respected_services {
+            options.respected_services = services.iter().cloned().collect();
+        }
+
+        if let Some(services) = &self.non_tree_services {
+            options.non_tree_services = services.iter().cloned().collect();
+        }
+
+        if let Some(services) = &self.settings_services {
+            options.settings_services = services.iter().cloned().collect();
+        }
+
+        options.filters.extend(self.filters.clone());
+
+        let project = &self.project;
+        let options = &mut options.project;
+
+        if project.name.is_some() {
+            options.name = project.name.clone();
+        }
+
+        if project.serve_port.is_some() {
+            options.serve_port = project.serve_port;
+        }
+
+        if !project.serve_place_ids.is_empty() {
+            options.serve_place_ids = project.serve_place_ids.clone();
+        }
+
+        if project.place_id.is_some() {
+            options.place_id = project.place_id;
+        }
+
+        if project.game_id.is_some() {
+            options.game_id = project.game_id;
+        }
+
+        if !project.glob_ignore_paths.is_empty() {
+            options.glob_ignore_paths = project.glob_ignore_paths.clone();
+        }
+    }
+
+    /// The export options this configuration describes.
+    pub fn options(&self) -> ExportOptions {
+        let mut options = ExportOptions::default();
+        self.apply(&mut options);
+        options
+    }
+}
@@ -0,0 +1,214 @@
+use crate::{
+    error::{Error, Operation},
+    filesystem::{Project, PROJECT_FILE, SRC},
+    report::{Decision, Report},
+    structures::*,
+};
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    path::{Path, PathBuf},
+};
+
+/// Something that would be written into the project folder.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PlannedEntry {
+    Folder,
+    File(Vec<u8>),
+}
+
+/// An `InstructionReader` that only records what would be written, so a conversion can be
+/// looked over before anything touches disk.
+#[derive(Clone, Debug)]
+pub struct DryRun {
+    project: Project,
+    entries: BTreeMap<PathBuf, PlannedEntry>,
+}
+
+impl DryRun {
+    pub fn new() -> Self {
+        Self {
+            project: Project::new(),
+            entries: BTreeMap::new(),
+        }
+    }
+
+    /// Every folder and file that would be written, relative to the project folder.
+    pub fn entries(&self) -> &BTreeMap<PathBuf, PlannedEntry> {
+        &self.entries
+    }
+
+    /// The would-be `default.project.json`, once the instructions are finished.
+    pub fn project_json(&self) -> Option<&str> {
+        match self.entries.get(Path::new(PROJECT_FILE)) {
+            Some(PlannedEntry::File(contents)) => std::str::from_utf8(contents).ok(),
+            _ => None,
+        }
+    }
+
+    /// The project folder as an indented tree, with the size of every file.
+    pub fn tree(&self) -> String {
+        let mut sizes: BTreeMap<&Path, Option<usize>> = BTreeMap::new();
+
+        for (path, entry) in &self.entries {
+            // Files are written without their folders being created first
+            for ancestor in path.ancestors().skip(1) {
+                if ancestor.as_os_str().is_empty() {
+                    break;
+                }
+
+                sizes.entry(ancestor).or_insert(None);
+            }
+
+            let size = match entry {
+                PlannedEntry::Folder => None,
+                PlannedEntry::File(contents) => Some(contents.len()),
+            };
+
+            sizes.insert(path.as_path(), size);
+        }
+
+        let mut tree = String::new();
+        for (path, size) in sizes {
+            let indent = "  ".repeat(path.components().count() - 1);
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+
+            let line = match size {
+                Some(size) => format!("{}{} ({})", indent, name, format_size(size)),
+                None => format!("{}{}/", indent, name),
+            };
+
+            tree.push_str(&line);
+            tree.push('\n');
+        }
+
+        tree
+    }
+
+    /// Everything a dry run has to say: the tree, the project file, and whatever the
+    /// conversion had to skip or couldn't place safely.
+    pub fn summary(&self, report: &Report) -> String {
+        let mut summary = self.tree();
+
+        let files = self
+            .entries
+            .values()
+            .filter_map(|entry| match entry {
+                PlannedEntry::File(contents) => Some(contents.len()),
+                PlannedEntry::Folder => None,
+            })
+            .collect::<Vec<_>>();
+
+        writeln!(
+            summary,
+            "\n{} files, {} in total",
+            files.len(),
+            format_size(files.iter().sum())
+        )
+        .unwrap();
+
+        if let Some(project) = self.project_json() {
+            writeln!(summary, "\n{}:\n{}", PROJECT_FILE, project).unwrap();
+        }
+
+        if !report.collisions.is_empty() {
+            writeln!(summary, "\nCollisions on case-insensitive filesystems:").unwrap();
+            for collision in &report.collisions {
+                writeln!(
+                    summary,
+                    "  {} overwrites {}",
+                    collision.path.display(),
+                    collision.existing.display()
+                )
+                .unwrap();
+            }
+        }
+
+        let mut skipped = report.with_decision(Decision::Skipped).peekable();
+        if skipped.peek().is_some() {
+            writeln!(summary, "\nSkipped instances:").unwrap();
+            for entry in skipped {
+                writeln!(
+                    summary,
+                    "  {} ({}): {}",
+                    entry.instance,
+                    entry.class_name,
+                    entry.reason.as_deref().unwrap_or_default()
+                )
+                .unwrap();
+            }
+        }
+
+        summary
+    }
+}
+
+impl Default for DryRun {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InstructionReader for DryRun {
+    fn read_instruction<'a>(&mut self, instruction: Instruction<'a>) -> Result<(), Error> {
+        match instruction {
+            Instruction::AddToTree { name, partition } => {
+                self.project.add_partition(name, partition);
+            }
+
+            Instruction::SetTreeRoot { name, partition } => {
+                self.project.set_root(name, partition);
+            }
+
+            Instruction::ConfigureProject { options } => {
+                self.project.configure(options);
+            }
+
+            Instruction::CreateFile { filename, contents } => {
+                self.entries.insert(
+                    Path::new(SRC).join(filename),
+                    PlannedEntry::File(contents.into_owned()),
+                );
+            }
+
+            Instruction::CreateFolder { folder } => {
+                self.entries
+                    .entry(Path::new(SRC).join(folder))
+                    .or_insert(PlannedEntry::Folder);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn finish_instructions(&mut self) -> Result<(), Error> {
+        let project = self
+            .project
+            .to_json()
+            .map_err(|error| Error::new(Operation::WriteProject, PROJECT_FILE, error.into()))?;
+
+        self.entries.insert(
+            PathBuf::from(PROJECT_FILE),
+            PlannedEntry::File(project.into_bytes()),
+        );
+
+        Ok(())
+    }
+}
+
+fn format_size(bytes: usize) -> String {
+    const UNITS: [&str; 3] = ["KiB", "MiB", "GiB"];
+
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    format!("{:.1} {}", size, UNITS[unit])
+}
@@ -0,0 +1,138 @@
+use std::{
+    fmt, io,
+    path::{Path, PathBuf},
+};
+
+/// What was being attempted when an `Error` happened.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Operation {
+    ReadFolder,
+    ReadFile,
+    CreateFolder,
+    CreateFile,
+    WriteFile,
+    DeleteFile,
+    WriteProject,
+    ReadManifest,
+    WriteManifest,
+    FindInstance,
+    BuildProject,
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str(match self {
+            Operation::ReadFolder => "read the folder",
+            Operation::ReadFile => "read the file",
+            Operation::CreateFolder => "create the folder",
+            Operation::CreateFile => "create the file",
+            Operation::WriteFile => "write to the file",
+            Operation::DeleteFile => "delete the file",
+            Operation::WriteProject => "write the project file",
+            Operation::ReadManifest => "read the manifest",
+            Operation::WriteManifest => "write the manifest",
+            Operation::FindInstance => "find the instance",
+            Operation::BuildProject => "build the project with Rojo",
+        })
+    }
+}
+
+/// What went wrong while attempting an `Operation`.
+#[derive(Debug)]
+pub enum Cause {
+    /// Reading, writing, or running something failed.
+    Io(io::Error),
+    /// Nothing in the tree has the DataModel path the error is about.
+    InstanceNotFound,
+}
+
+impl fmt::Display for Cause {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Cause::Io(error) => write!(formatter, "{}", error),
+            Cause::InstanceNotFound => formatter.write_str("nothing in the tree has this path"),
+        }
+    }
+}
+
+/// An error from writing or reading a converted project, with enough context to find what
+/// caused it.
+#[derive(Debug)]
+pub struct Error {
+    operation: Operation,
+    path: PathBuf,
+    instance: Option<String>,
+    cause: Cause,
+}
+
+impl Error {
+    pub fn new(operation: Operation, path: impl Into<PathBuf>, source: io::Error) -> Self {
+        Self {
+            operation,
+            path: path.into(),
+            instance: None,
+            cause: Cause::Io(source),
+        }
+    }
+
+    /// An instance looked up by its DataModel path, e.g. `Workspace.Map`, doesn't exist.
+    pub fn instance_not_found(path: impl Into<PathBuf>) -> Self {
+        Self {
+            operation: Operation::FindInstance,
+            path: path.into(),
+            instance: None,
+            cause: Cause::InstanceNotFound,
+        }
+    }
+
+    /// Records the DataModel path of the instance being written, e.g. `Workspace.Map.Door`.
+    /// The innermost instance wins, so this does nothing if one is already set.
+    pub fn with_instance(mut self, instance: impl Into<String>) -> Self {
+        if self.instance.is_none() {
+            self.instance = Some(instance.into());
+        }
+        self
+    }
+
+    pub fn operation(&self) -> Operation {
+        self.operation
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn instance(&self) -> Option<&str> {
+        self.instance.as_deref()
+    }
+
+    pub fn cause(&self) -> &Cause {
+        &self.cause
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "couldn't {} {}",
+            self.operation,
+            self.path.display()
+        )?;
+
+        if let Some(instance) = &self.instance {
+            write!(formatter, " for {}", instance)?;
+        }
+
+        write!(formatter, ": {}", self.cause)
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.cause {
+            Cause::Io(error) => Some(error),
+            Cause::InstanceNotFound => None,
+        }
+    }
+}
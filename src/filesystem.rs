@@ -1,13 +1,19 @@
-use crate::structures::*;
-use serde::{ser::SerializeMap, Serialize, Serializer};
+use crate::{
+    error::{Error, Operation},
+    structures::*,
+};
+use log::{info, warn};
+use serde::{ser::SerializeMap, Deserialize, Serialize, Serializer};
 use std::{
-    collections::BTreeMap,
+    collections::{BTreeMap, BTreeSet},
     fs::{self, File},
-    io::Write,
-    path::PathBuf,
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
 };
 
-const SRC: &str = "src";
+pub(crate) const SRC: &str = "src";
+pub(crate) const PROJECT_FILE: &str = "default.project.json";
+const MANIFEST: &str = "rbxlx-to-rojo.manifest.json";
 
 fn serialize_project_tree<S: Serializer>(
     tree: &BTreeMap<String, TreePartition>,
@@ -22,26 +28,160 @@ fn serialize_project_tree<S: Serializer>(
 }
 
 #[derive(Clone, Debug, Serialize)]
-struct Project {
-    name: String,
+#[serde(untagged)]
+enum ProjectTree {
+    /// A place, with services under a DataModel.
     #[serde(serialize_with = "serialize_project_tree")]
-    tree: BTreeMap<String, TreePartition>,
+    Place(BTreeMap<String, TreePartition>),
+    /// A model, whose root is the partition itself.
+    Model(TreePartition),
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Project {
+    name: String,
+    /// Whether the name was configured, as opposed to the default.
+    #[serde(skip)]
+    named: bool,
+    tree: ProjectTree,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    serve_port: Option<u16>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    serve_place_ids: Vec<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    place_id: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    game_id: Option<u64>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    glob_ignore_paths: Vec<String>,
+}
+
+/// Points every path in a partition inside the `src` folder.
+fn prefix_paths(partition: &mut TreePartition) {
+    if let Some(path) = partition.path.take() {
+        partition.path = Some(PathBuf::from(SRC).join(path));
+    }
+
+    for child in partition.children.values_mut() {
+        prefix_paths(child);
+    }
 }
 
 impl Project {
-    fn new() -> Self {
+    pub(crate) fn new() -> Self {
         Self {
             name: "project".to_string(),
-            tree: BTreeMap::new(),
+            named: false,
+            tree: ProjectTree::Place(BTreeMap::new()),
+            serve_port: None,
+            serve_place_ids: Vec::new(),
+            place_id: None,
+            game_id: None,
+            glob_ignore_paths: Vec::new(),
+        }
+    }
+
+    /// Adds a partition to the tree, pointing its paths inside the `src` folder.
+    pub(crate) fn add_partition(&mut self, mut name: String, mut partition: TreePartition) {
+        let tree = match &mut self.tree {
+            ProjectTree::Place(tree) => tree,
+            ProjectTree::Model(_) => {
+                warn!("{} can't be added to a model project, leaving it out", name);
+                return;
+            }
+        };
+
+        if tree.contains_key(&name) {
+            let original = name.clone();
+            let mut counter = 2;
+            loop {
+                let candidate = format!("{}_{}", original, counter);
+                if !tree.contains_key(&candidate) {
+                    name = candidate;
+                    break;
+                }
+                counter += 1;
+            }
+
+            if let Some(path) = partition.path.take() {
+                let new_path = match path.parent() {
+                    Some(parent) => parent.join(&name),
+                    None => PathBuf::from(&name),
+                };
+                partition.path = Some(new_path);
+            }
+        }
+
+        prefix_paths(&mut partition);
+        tree.insert(name, partition);
+    }
+
+    /// Turns the project into a model project named `name`, with `partition` as its tree.
+    pub(crate) fn set_root(&mut self, name: String, mut partition: TreePartition) {
+        // Rojo names the model after the project, so any other name would rename it
+        if self.named && self.name != name {
+            warn!(
+                "model projects are named after their root, naming the project {} instead of {}",
+                name, self.name
+            );
         }
+
+        prefix_paths(&mut partition);
+        self.name = name;
+        self.tree = ProjectTree::Model(partition);
+    }
+
+    pub(crate) fn configure(&mut self, options: ProjectOptions) {
+        if let Some(name) = options.name {
+            self.name = name;
+            self.named = true;
+        }
+
+        self.serve_port = options.serve_port;
+        self.serve_place_ids = options.serve_place_ids;
+        self.place_id = options.place_id;
+        self.game_id = options.game_id;
+        self.glob_ignore_paths = options.glob_ignore_paths;
+    }
+
+    /// The contents of `default.project.json`.
+    pub(crate) fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+/// Every file and folder rbxlx-to-rojo wrote into a project, relative to the project root.
+/// Updates use it to tell their own files apart from ones added by hand.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub(crate) struct Manifest {
+    pub(crate) files: BTreeSet<String>,
+    pub(crate) folders: BTreeSet<String>,
+}
+
+impl Manifest {
+    /// Reads the manifest of the project in `root`.
+    pub(crate) fn read(root: &Path) -> Result<Self, Error> {
+        let manifest_path = root.join(MANIFEST);
+        fs::read(&manifest_path)
+            .and_then(|contents| Ok(serde_json::from_slice(&contents)?))
+            .map_err(|error| Error::new(Operation::ReadManifest, manifest_path, error))
     }
 }
 
+fn manifest_key(path: &Path) -> String {
+    path.to_string_lossy().replace("\\", "/")
+}
+
 #[derive(Clone, Debug)]
 pub struct FileSystem {
     project: Project,
     root: PathBuf,
     source: PathBuf,
+    manifest: Manifest,
+    /// The manifest of the export being updated, if this is an update.
+    previous: Option<Manifest>,
 }
 
 impl FileSystem {
@@ -55,84 +195,143 @@ impl FileSystem {
             project,
             root,
             source,
+            manifest: Manifest::default(),
+            previous: None,
         }
     }
-}
 
-impl InstructionReader for FileSystem {
-    fn read_instruction<'a>(&mut self, instruction: Instruction<'a>) {
-        match instruction {
-            Instruction::AddToTree {
-                mut name,
-                mut partition,
-            } => {
-                if self.project.tree.contains_key(&name) {
-                    let original = name.clone();
-                    let mut counter = 2;
-                    loop {
-                        let candidate = format!("{}_{}", original, counter);
-                        if !self.project.tree.contains_key(&candidate) {
-                            name = candidate;
-                            break;
-                        }
-                        counter += 1;
-                    }
-
-                    if let Some(path) = partition.path.take() {
-                        let new_path = match path.parent() {
-                            Some(parent) => parent.join(&name),
-                            None => PathBuf::from(&name),
-                        };
-                        partition.path = Some(new_path);
-                    }
-                }
+    /// Updates a project from an earlier export in place. Only files whose contents changed
+    /// are written, files of instances that are gone are deleted, and files the earlier
+    /// export didn't write are never touched.
+    pub fn update_root(root: PathBuf) -> Result<Self, Error> {
+        let manifest = Manifest::read(&root)?;
+
+        Ok(Self {
+            previous: Some(manifest),
+            ..Self::from_root(root)
+        })
+    }
+
+    fn record_folders(&mut self, folder: &Path) {
+        for ancestor in folder.ancestors() {
+            if ancestor.as_os_str().is_empty()
+                || !self.manifest.folders.insert(manifest_key(ancestor))
+            {
+                break;
+            }
+        }
+    }
+
+    /// Writes a file relative to the project root, unless an update finds it already has
+    /// these contents or it isn't one of ours.
+    fn write_file(&mut self, relative_path: &Path, contents: &[u8]) -> Result<(), Error> {
+        let full_path = self.root.join(relative_path);
+        let key = manifest_key(relative_path);
 
-                if let Some(path) = partition.path {
-                    partition.path = Some(PathBuf::from(SRC).join(path));
+        if let Some(previous) = &self.previous {
+            match fs::read(&full_path) {
+                Ok(existing) if existing == contents => {
+                    self.manifest.files.insert(key);
+                    return Ok(());
                 }
 
-                for child in partition.children.values_mut() {
-                    if let Some(path) = &child.path {
-                        child.path = Some(PathBuf::from(SRC).join(path));
-                    }
+                Ok(_) if !previous.files.contains(&key) => {
+                    warn!(
+                        "{} wasn't written by rbxlx-to-rojo, leaving it alone",
+                        full_path.display()
+                    );
+                    return Ok(());
                 }
 
-                self.project.tree.insert(name, partition);
+                _ => {}
             }
+        }
 
-            Instruction::CreateFile { filename, contents } => {
-                let full_path = self.source.join(&filename);
+        if let Some(parent) = full_path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|error| Error::new(Operation::CreateFolder, parent, error))?;
+        }
 
-                if let Some(parent) = full_path.parent() {
-                    fs::create_dir_all(parent).unwrap_or_else(|error| {
-                        panic!("can't create parent dirs for {:?}: {:?}", full_path, error)
-                    });
-                }
+        let mut file = File::create(&full_path)
+            .map_err(|error| Error::new(Operation::CreateFile, &full_path, error))?;
+        file.write_all(contents)
+            .map_err(|error| Error::new(Operation::WriteFile, &full_path, error))?;
+
+        if let Some(parent) = relative_path.parent() {
+            self.record_folders(parent);
+        }
+        self.manifest.files.insert(key);
+
+        Ok(())
+    }
+
+    /// Deletes whatever the previous export wrote that this one didn't.
+    fn remove_stale(&self, previous: &Manifest) -> Result<(), Error> {
+        for stale in previous.files.difference(&self.manifest.files) {
+            let full_path = self.root.join(stale);
+            match fs::remove_file(&full_path) {
+                Ok(()) => info!("removed {}", full_path.display()),
+                Err(error) if error.kind() == ErrorKind::NotFound => {}
+                Err(error) => return Err(Error::new(Operation::DeleteFile, full_path, error)),
+            }
+        }
+
+        // Deepest first, and only once they're empty, so hand-added files keep their folders
+        for stale in previous.folders.difference(&self.manifest.folders).rev() {
+            fs::remove_dir(self.root.join(stale)).ok();
+        }
+
+        Ok(())
+    }
+}
+
+impl InstructionReader for FileSystem {
+    fn read_instruction<'a>(&mut self, instruction: Instruction<'a>) -> Result<(), Error> {
+        match instruction {
+            Instruction::AddToTree { name, partition } => {
+                self.project.add_partition(name, partition);
+            }
 
-                let mut file = File::create(&full_path).unwrap_or_else(|error| {
-                    panic!("can't create file {:?}: {:?}", full_path, error)
-                });
-                file.write_all(&contents).unwrap_or_else(|error| {
-                    panic!("can't write to file {:?} due to {:?}", filename, error)
-                });
+            Instruction::SetTreeRoot { name, partition } => {
+                self.project.set_root(name, partition);
+            }
+
+            Instruction::ConfigureProject { options } => {
+                self.project.configure(options);
+            }
+
+            Instruction::CreateFile { filename, contents } => {
+                self.write_file(&Path::new(SRC).join(&filename), &contents)?;
             }
 
             Instruction::CreateFolder { folder } => {
-                fs::create_dir_all(self.source.join(&folder)).unwrap_or_else(|error| {
-                    panic!("can't write to folder {:?}: {:?}", folder, error)
-                });
+                let full_path = self.source.join(&folder);
+                fs::create_dir_all(&full_path)
+                    .map_err(|error| Error::new(Operation::CreateFolder, full_path, error))?;
+                self.record_folders(&Path::new(SRC).join(&folder));
             }
         }
+
+        Ok(())
     }
 
-    fn finish_instructions(&mut self) {
-        let mut file = File::create(self.root.join("default.project.json"))
-            .expect("can't create default.project.json");
-        file.write_all(
-            &serde_json::to_string_pretty(&self.project)
-                .expect("couldn't serialize project")
-                .as_bytes(),
-        )
-        .expect("can't write project");
+    fn finish_instructions(&mut self) -> Result<(), Error> {
+        let path = self.root.join(PROJECT_FILE);
+        let project = self
+            .project
+            .to_json()
+            .map_err(|error| Error::new(Operation::WriteProject, &path, error.into()))?;
+
+        self.write_file(Path::new(PROJECT_FILE), project.as_bytes())?;
+
+        if let Some(previous) = self.previous.take() {
+            self.remove_stale(&previous)?;
+        }
+
+        let manifest_path = self.root.join(MANIFEST);
+        let manifest = serde_json::to_string_pretty(&self.manifest)
+            .map_err(|error| Error::new(Operation::WriteManifest, &manifest_path, error.into()))?;
+        fs::write(&manifest_path, manifest)
+            .map_err(|error| Error::new(Operation::WriteManifest, manifest_path, error))
     }
 }
@@ -0,0 +1,185 @@
+use crate::{config::read_toml, error::Error};
+use rbx_dom_weak::{types::Ref, Instance, WeakDom};
+use serde::Deserialize;
+use std::{collections::HashSet, path::Path};
+
+/// Rules for which instances make it into the project, on top of the export mode.
+///
+/// Paths are DataModel path globs, with segments separated by `.` or `/`. `*` and `?` match
+/// within a name, and `**` matches any number of names, so `Workspace.Map.**` is the map and
+/// everything in it, and `**/ThirdParty/**` is any `ThirdParty` anywhere. Classes match an
+/// instance of that class or of any class inheriting from it, so `BasePart` matches a `Part`.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Filters {
+    /// When there are any include rules, only instances matching one of them are exported,
+    /// along with their descendants and the ancestors leading to them.
+    pub include: Vec<String>,
+    pub include_classes: Vec<String>,
+    /// Instances matching an exclude rule are left out with all of their descendants, even
+    /// if they're included.
+    pub exclude: Vec<String>,
+    pub exclude_classes: Vec<String>,
+}
+
+/// Whether one name matches one segment of a pattern.
+fn segment_matches(pattern: &[char], name: &[char]) -> bool {
+    match (pattern.first(), name.first()) {
+        (None, None) => true,
+        (Some('*'), _) => {
+            segment_matches(&pattern[1..], name)
+                || (!name.is_empty() && segment_matches(pattern, &name[1..]))
+        }
+        (Some('?'), Some(_)) => segment_matches(&pattern[1..], &name[1..]),
+        (Some(expected), Some(actual)) => {
+            expected == actual && segment_matches(&pattern[1..], &name[1..])
+        }
+        _ => false,
+    }
+}
+
+fn glob_matches(pattern: &[&str], path: &[&str]) -> bool {
+    match pattern.first() {
+        None => path.is_empty(),
+        Some(&"**") => {
+            glob_matches(&pattern[1..], path)
+                || (!path.is_empty() && glob_matches(pattern, &path[1..]))
+        }
+        Some(segment) => match path.first() {
+            Some(name) => {
+                let segment: Vec<char> = segment.chars().collect();
+                let name: Vec<char> = name.chars().collect();
+                segment_matches(&segment, &name) && glob_matches(&pattern[1..], &path[1..])
+            }
+            None => false,
+        },
+    }
+}
+
+fn split_pattern(pattern: &str) -> Vec<&str> {
+    pattern.split(|c| c == '.' || c == '/').collect()
+}
+
+/// Whether `instance` is a `class_name`, going by the reflection database.
+fn is_a(instance: &Instance, class_name: &str) -> bool {
+    if instance.class == class_name {
+        return true;
+    }
+
+    let db = match rbx_reflection_database::get() {
+        Ok(db) => db,
+        Err(_) => return false,
+    };
+
+    let mut current = db.classes.get(instance.class.as_str());
+    while let Some(class) = current {
+        if class.name == class_name {
+            return true;
+        }
+
+        current = class
+            .superclass
+            .as_ref()
+            .and_then(|superclass| db.classes.get(superclass.as_ref()));
+    }
+
+    false
+}
+
+impl Filters {
+    /// Reads filters from a TOML file with `include`, `include_classes`, `exclude` and
+    /// `exclude_classes` lists.
+    pub fn from_file(path: &Path) -> Result<Self, Error> {
+        read_toml(path)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty()
+            && self.include_classes.is_empty()
+            && self.exclude.is_empty()
+            && self.exclude_classes.is_empty()
+    }
+
+    /// Adds the rules of `other` to these.
+    pub fn extend(&mut self, other: Filters) {
+        self.include.extend(other.include);
+        self.include_classes.extend(other.include_classes);
+        self.exclude.extend(other.exclude);
+        self.exclude_classes.extend(other.exclude_classes);
+    }
+
+    fn matches(
+        patterns: &[String],
+        classes: &[String],
+        instance: &Instance,
+        path: &[&str],
+    ) -> bool {
+        patterns
+            .iter()
+            .any(|pattern| glob_matches(&split_pattern(pattern), path))
+            || classes.iter().any(|class_name| is_a(instance, class_name))
+    }
+
+    /// Whether an instance matches the include rules, given its DataModel path.
+    pub fn includes(&self, instance: &Instance, path: &[&str]) -> bool {
+        Self::matches(&self.include, &self.include_classes, instance, path)
+    }
+
+    /// Whether an instance matches the exclude rules, given its DataModel path.
+    pub fn excludes(&self, instance: &Instance, path: &[&str]) -> bool {
+        Self::matches(&self.exclude, &self.exclude_classes, instance, path)
+    }
+
+    /// Collects what the filters leave out below `instance`. Returns whether anything below
+    /// it is kept.
+    fn collect_excluded<'a>(
+        &self,
+        tree: &'a WeakDom,
+        instance: &'a Instance,
+        path: &mut Vec<&'a str>,
+        included: bool,
+        excluded: &mut HashSet<Ref>,
+    ) -> bool {
+        let mut any_kept = false;
+
+        for child_id in instance.children() {
+            let child = tree.get_by_ref(*child_id).expect("got fake child id?");
+            path.push(child.name.as_str());
+
+            let kept = if self.excludes(child, path) {
+                false
+            } else {
+                let included = included || self.includes(child, path);
+                self.collect_excluded(tree, child, path, included, excluded) || included
+            };
+
+            if !kept {
+                excluded.insert(*child_id);
+            }
+
+            any_kept = any_kept || kept;
+            path.pop();
+        }
+
+        any_kept
+    }
+
+    /// Every instance in `tree` the filters leave out. An instance being in the set means
+    /// its descendants are left out too, though they aren't necessarily in it themselves.
+    pub fn excluded_instances(&self, tree: &WeakDom) -> HashSet<Ref> {
+        let mut excluded = HashSet::new();
+
+        if !self.is_empty() {
+            let everything_included = self.include.is_empty() && self.include_classes.is_empty();
+            self.collect_excluded(
+                tree,
+                tree.root(),
+                &mut Vec::new(),
+                everything_included,
+                &mut excluded,
+            );
+        }
+
+        excluded
+    }
+}
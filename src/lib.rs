@@ -1,65 +1,182 @@
-use log::{debug, warn};
+use log::{debug, info, warn};
 use rbx_dom_weak::{
     types::{Ref, Variant},
     Instance, InstanceBuilder, WeakDom,
 };
 use rbx_reflection::ClassTag;
+use serde::Deserialize;
+use serde_json::json;
 use std::{
     borrow::Cow,
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
     path::{Path, PathBuf},
 };
 
+use error::Error;
+use filesystem::PROJECT_FILE;
+use filter::Filters;
+use properties::{
+    encode_attributes, encode_properties, instance_attributes, instance_properties, Properties,
+};
+use report::{Collision, Decision, InstanceReport, Report, SkipReason};
 use structures::*;
 
+pub mod check;
+pub mod config;
+pub mod dry_run;
+pub mod error;
 pub mod filesystem;
+pub mod filter;
+mod properties;
+pub mod report;
 pub mod structures;
+pub mod verify;
 
 #[cfg(test)]
 mod tests;
 
-lazy_static::lazy_static! {
-    static ref NON_TREE_SERVICES: HashSet<&'static str> = include_str!("./non-tree-services.txt").lines().collect();
-    #[allow(dead_code)]
-    static ref RESPECTED_SERVICES: HashSet<&'static str> = include_str!("./respected-services.txt").lines().collect();
+const NON_TREE_SERVICES: &str = include_str!("./non-tree-services.txt");
+const RESPECTED_SERVICES: &str = include_str!("./respected-services.txt");
+const SETTINGS_SERVICES: &str = include_str!("./settings-services.txt");
+
+fn service_list(list: &str) -> HashSet<String> {
+    list.lines().map(str::to_owned).collect()
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum ExportMode {
     Full,
     ScriptsOnly,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ScriptExtension {
+    Lua,
+    Luau,
+}
+
+impl ScriptExtension {
+    fn as_str(self) -> &'static str {
+        match self {
+            ScriptExtension::Lua => "lua",
+            ScriptExtension::Luau => "luau",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ModelFormat {
+    /// `.rbxmx`, larger but diffable.
+    Xml,
+    /// `.rbxm`, much smaller and faster for Rojo to load.
+    Binary,
+}
+
+impl ModelFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            ModelFormat::Xml => "rbxmx",
+            ModelFormat::Binary => "rbxm",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ExportOptions {
+    pub mode: ExportMode,
+    /// The extension every script file in the project is written with.
+    pub script_extension: ScriptExtension,
+    /// The format every model file in the project is written in.
+    pub model_format: ModelFormat,
+    /// Subtrees with at most this many instances are written as readable `.model.json` files
+    /// instead, as long as all of their properties can be expressed in JSON.
+    pub model_json_threshold: Option<usize>,
+    /// Containers holding scripts, like a Tool or a ScreenGui, are written as folders whose
+    /// `init.meta.json` carries their class and properties, instead of as a model with a project
+    /// file. Containers with properties meta files can't express still get a model.
+    pub containers_as_folders: bool,
+    /// DataModel paths of the instances to export, e.g. `ServerScriptService.Combat`, instead
+    /// of the whole tree. Their ancestors are only kept as nodes of the project tree.
+    pub subtrees: Vec<String>,
+    /// Include and exclude rules for which instances are exported at all.
+    pub filters: Filters,
+    /// Services that are exported. Every other service is left out.
+    pub respected_services: HashSet<String>,
+    /// Services that are written as folders but not added to the project tree, since Rojo
+    /// syncs them through another service, like StarterPlayerScripts through StarterPlayer.
+    pub non_tree_services: HashSet<String>,
+    /// Services that aren't exported, but whose settings, like `Players.CharacterAutoLoads`,
+    /// are still kept in the project tree.
+    pub settings_services: HashSet<String>,
+    /// Settings for `default.project.json`.
+    pub project: ProjectOptions,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        Self {
+            mode: ExportMode::Full,
+            script_extension: ScriptExtension::Luau,
+            model_format: ModelFormat::Xml,
+            model_json_threshold: None,
+            containers_as_folders: false,
+            subtrees: Vec::new(),
+            filters: Filters::default(),
+            respected_services: service_list(RESPECTED_SERVICES),
+            non_tree_services: service_list(NON_TREE_SERVICES),
+            settings_services: service_list(SETTINGS_SERVICES),
+            project: ProjectOptions::default(),
+        }
+    }
+}
+
 struct TreeIterator<'a, I: InstructionReader + ?Sized> {
     instruction_reader: &'a mut I,
     path: &'a Path,
     tree: &'a WeakDom,
-    mode: ExportMode,
+    options: &'a ExportOptions,
+    report: &'a mut Report,
+    /// What the filters leave out.
+    excluded: &'a HashSet<Ref>,
 }
 
 #[derive(Clone, Copy)]
 enum ChildTraversal {
     Normal,
+    /// Only what holds scripts is visited, the rest is in the instance's model or not exported.
     ScriptsOnly,
     Skip,
+    /// Every descendant is already in the instance's model file.
+    Packed,
 }
 
 struct Representation<'a> {
     instructions: Vec<Instruction<'a>>,
     path: Cow<'a, Path>,
     traversal: ChildTraversal,
+    /// The model file the instance and its non-script descendants were packed into, if any.
+    model: Option<PathBuf>,
+    /// Whether the model is in a folder of its own, with a `default.project.json` that builds
+    /// the instance from it, named as it should be, and with whatever is written beside it as
+    /// its children.
+    project: bool,
+    /// Anything worth knowing about how the instance was written, for the report.
+    notes: Vec<String>,
 }
 
 const WINDOWS_RESERVED: [&str; 22] = [
-    "con", "prn", "aux", "nul", "com1", "com2", "com3", "com4", "com5", "com6", "com7", "com8", "com9",
-    "lpt1", "lpt2", "lpt3", "lpt4", "lpt5", "lpt6", "lpt7", "lpt8", "lpt9",
+    "con", "prn", "aux", "nul", "com1", "com2", "com3", "com4", "com5", "com6", "com7", "com8",
+    "com9", "lpt1", "lpt2", "lpt3", "lpt4", "lpt5", "lpt6", "lpt7", "lpt8", "lpt9",
 ];
 
 fn is_script_class(class_name: &str) -> bool {
     matches!(class_name, "Script" | "LocalScript" | "ModuleScript")
 }
 
-fn should_skip_service(class_name: &str) -> bool {
+fn should_skip_service(class_name: &str, respected_services: &HashSet<String>) -> bool {
     let db = match rbx_reflection_database::get() {
         Ok(db) => db,
         Err(error) => {
@@ -69,7 +186,9 @@ fn should_skip_service(class_name: &str) -> bool {
     };
 
     match db.classes.get(class_name) {
-        Some(reflected) => reflected.tags.contains(&ClassTag::Service) && !RESPECTED_SERVICES.contains(class_name),
+        Some(reflected) => {
+            reflected.tags.contains(&ClassTag::Service) && !respected_services.contains(class_name)
+        }
         None => false,
     }
 }
@@ -100,22 +219,228 @@ fn sanitize_component(name: &str) -> String {
     sanitized
 }
 
-fn sanitized_join(base: &Path, name: &str) -> PathBuf {
-    base.join(sanitize_component(name))
+/// The file name Rojo expects for a script of the given class, e.g. `Name.server.luau`.
+/// Scripts with children use `init` as their stem.
+fn script_file_name(class_name: &str, stem: &str, extension: ScriptExtension) -> String {
+    let kind = match class_name {
+        "Script" => ".server",
+        "LocalScript" => ".client",
+        _ => "",
+    };
+
+    format!("{}{}.{}", stem, kind, extension.as_str())
+}
+
+/// The key two stems are compared by. Names that only differ in case collide on Windows and
+/// macOS, and Rojo reads suffixes like `.server` as part of the file type rather than the name.
+fn collision_key(stem: &str) -> String {
+    let mut key = stem.to_lowercase();
+
+    for suffix in [".server", ".client", ".meta", ".model"] {
+        if key.ends_with(suffix) {
+            key.truncate(key.len() - suffix.len());
+            break;
+        }
+    }
+
+    key
 }
 
-fn clone_without_scripts(
-    source: &WeakDom,
-    source_ref: Ref,
-    target: &mut WeakDom,
-    parent: Ref,
-) -> Option<Ref> {
-    let instance = source.get_by_ref(source_ref)?;
+/// Picks a unique file name stem for every child of `instance`, in child order.
+/// A child keeps its sanitized name unless an earlier sibling already claimed it, in which
+/// case it gets the first free `Name_2`, `Name_3`, ... that no other sibling uses.
+/// `init` is always claimed, since Rojo would read it as the parent's own file.
+fn allocate_stems(tree: &WeakDom, instance: &Instance) -> HashMap<Ref, String> {
+    let natural_stems: Vec<(Ref, String)> = instance
+        .children()
+        .iter()
+        .map(|child_id| {
+            let child = tree.get_by_ref(*child_id).expect("got fake child id?");
+            (*child_id, sanitize_component(&child.name))
+        })
+        .collect();
+
+    let natural: HashSet<String> = natural_stems
+        .iter()
+        .map(|(_, stem)| collision_key(stem))
+        .collect();
+
+    let mut claimed = HashSet::new();
+    claimed.insert("init".to_string());
+
+    let mut stems = HashMap::new();
+
+    for (child_id, stem) in &natural_stems {
+        let stem = if claimed.insert(collision_key(stem)) {
+            stem.clone()
+        } else {
+            let mut counter = 2;
+            loop {
+                let candidate = format!("{}_{}", stem, counter);
+                let key = collision_key(&candidate);
+                if !natural.contains(&key) && claimed.insert(key) {
+                    break candidate;
+                }
+                counter += 1;
+            }
+        };
+
+        stems.insert(*child_id, stem);
+    }
+
+    stems
+}
+
+/// The meta file for an instance written under `stem`. Whenever the stem isn't exactly the
+/// instance's name, because it was sanitized or deduplicated, the original name is recorded
+/// so Rojo rebuilds the instance with it.
+fn instance_meta(instance: &Instance, stem: &str) -> MetaFile {
+    let mut meta = MetaFile::from_instance(instance);
+    if stem != instance.name {
+        meta.name = Some(instance.name.clone());
+    }
+    meta
+}
 
-    if is_script_class(instance.class.as_str()) {
+/// The `init.meta.json` that turns a folder into `instance`, carrying its class and
+/// properties, or `None` if it has values a meta file can't hold.
+fn container_meta(instance: &Instance, stem: &str) -> Option<MetaFile> {
+    let (properties, unrepresentable_properties) = encode_properties(instance);
+    let (attributes, unrepresentable_attributes) = encode_attributes(instance);
+
+    if !unrepresentable_properties.is_empty() || !unrepresentable_attributes.is_empty() {
+        debug!(
+            "{} has values meta files can't hold ({}), falling back to a model file",
+            instance.name,
+            unrepresentable_properties
+                .iter()
+                .chain(unrepresentable_attributes.iter())
+                .cloned()
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
         return None;
     }
 
+    Some(MetaFile {
+        name: (stem != instance.name).then(|| instance.name.clone()),
+        class_name: Some(instance.class.to_string()),
+        properties,
+        attributes,
+        ..MetaFile::default()
+    })
+}
+
+fn full_name(tree: &WeakDom, instance: &Instance) -> String {
+    let mut names = vec![instance.name.as_str()];
+    let mut parent = instance.parent();
+
+    while let Some(ancestor) = tree.get_by_ref(parent) {
+        if ancestor.referent() == tree.root_ref() {
+            break;
+        }
+
+        names.push(ancestor.name.as_str());
+        parent = ancestor.parent();
+    }
+
+    names.reverse();
+    names.join(".")
+}
+
+/// Finds an instance by its DataModel path. Names with dots in them are matched whole before
+/// the path is split any further.
+fn find_by_path<'a>(tree: &'a WeakDom, path: &str) -> Option<&'a Instance> {
+    let segments: Vec<&str> = path.split('.').collect();
+    let mut current = tree.root();
+    let mut start = 0;
+
+    while start < segments.len() {
+        let (child, end) = (start + 1..=segments.len()).rev().find_map(|end| {
+            let name = segments[start..end].join(".");
+            current
+                .children()
+                .iter()
+                .map(|child_id| tree.get_by_ref(*child_id).expect("got fake child id?"))
+                .find(|child| child.name == name)
+                .map(|child| (child, end))
+        })?;
+
+        current = child;
+        start = end;
+    }
+
+    Some(current)
+}
+
+/// The ancestors of an instance below the root, outermost first, ending with the instance.
+fn ancestry<'a>(tree: &'a WeakDom, instance: &'a Instance) -> Vec<&'a Instance> {
+    let mut chain = vec![instance];
+    let mut parent = instance.parent();
+
+    while let Some(ancestor) = tree.get_by_ref(parent) {
+        if ancestor.referent() == tree.root_ref() {
+            break;
+        }
+
+        chain.push(ancestor);
+        parent = ancestor.parent();
+    }
+
+    chain.reverse();
+    chain
+}
+
+fn is_service(class_name: &str) -> bool {
+    rbx_reflection_database::get()
+        .ok()
+        .and_then(|db| db.classes.get(class_name))
+        .map_or(false, |class| class.tags.contains(&ClassTag::Service))
+}
+
+/// Adds a partition to a tree, merging it into a partition of the same name if there is one.
+fn merge_partition(
+    tree: &mut BTreeMap<String, TreePartition>,
+    name: String,
+    partition: TreePartition,
+) {
+    match tree.get_mut(&name) {
+        Some(existing) => {
+            for (child_name, child) in partition.children {
+                merge_partition(&mut existing.children, child_name, child);
+            }
+        }
+
+        None => {
+            tree.insert(name, partition);
+        }
+    }
+}
+
+/// Whether a descendant is left out of its ancestor's model file. Scripts and anything
+/// holding them get files of their own, and whatever the filters exclude isn't exported.
+fn left_out_of_model(
+    instance: &Instance,
+    has_scripts: &HashMap<Ref, bool>,
+    excluded: &HashSet<Ref>,
+) -> bool {
+    is_script_class(instance.class.as_str())
+        || excluded.contains(&instance.referent())
+        || has_scripts
+            .get(&instance.referent())
+            .copied()
+            .unwrap_or(false)
+}
+
+/// Copies an instance and the descendants that belong in its model into `target`.
+fn clone_without_scripts(
+    source: &WeakDom,
+    instance: &Instance,
+    target: &mut WeakDom,
+    parent: Ref,
+    has_scripts: &HashMap<Ref, bool>,
+    excluded: &HashSet<Ref>,
+) -> Ref {
     let mut builder = InstanceBuilder::new(instance.class.clone()).with_name(instance.name.clone());
 
     for (key, value) in instance.properties.iter() {
@@ -125,82 +450,375 @@ fn clone_without_scripts(
     let new_ref = target.insert(parent, builder);
 
     for child_ref in instance.children() {
-        clone_without_scripts(source, *child_ref, target, new_ref);
+        let child = source.get_by_ref(*child_ref).expect("got fake child id?");
+        if !left_out_of_model(child, has_scripts, excluded) {
+            clone_without_scripts(source, child, target, new_ref, has_scripts, excluded);
+        }
     }
 
-    Some(new_ref)
+    new_ref
 }
 
-fn serialize_instance_to_rbxm(tree: &WeakDom, instance: &Instance) -> Option<Vec<u8>> {
+fn serialize_model(
+    tree: &WeakDom,
+    instance: &Instance,
+    format: ModelFormat,
+    has_scripts: &HashMap<Ref, bool>,
+    excluded: &HashSet<Ref>,
+) -> Option<Vec<u8>> {
     let mut dom = WeakDom::new(InstanceBuilder::new("DataModel").with_name("DataModel"));
     let dom_root = dom.root_ref();
-
-    let Some(root_ref) = clone_without_scripts(tree, instance.referent(), &mut dom, dom_root) else {
-        return None;
-    };
+    let root_ref = clone_without_scripts(tree, instance, &mut dom, dom_root, has_scripts, excluded);
 
     let mut bytes = Vec::new();
 
-    match rbx_xml::to_writer_default(&mut bytes, &dom, &[root_ref]) {
+    let result = match format {
+        ModelFormat::Xml => rbx_xml::to_writer_default(&mut bytes, &dom, &[root_ref])
+            .map_err(|error| format!("{:?}", error)),
+        ModelFormat::Binary => rbx_binary::to_writer(&mut bytes, &dom, &[root_ref])
+            .map_err(|error| format!("{:?}", error)),
+    };
+
+    match result {
         Ok(()) => Some(bytes),
         Err(error) => {
-            warn!("couldn't serialize {} to rbxm: {:?}", instance.name, error);
+            warn!(
+                "couldn't serialize {} to {}: {}",
+                instance.name,
+                format.extension(),
+                error
+            );
             None
         }
     }
 }
+
+fn build_model_json(
+    tree: &WeakDom,
+    instance: &Instance,
+    remaining: &mut usize,
+    has_scripts: &HashMap<Ref, bool>,
+    excluded: &HashSet<Ref>,
+) -> Option<ModelJson> {
+    if *remaining == 0 {
+        return None;
+    }
+    *remaining -= 1;
+
+    let (properties, unrepresentable_properties) = encode_properties(instance);
+    let (attributes, unrepresentable_attributes) = encode_attributes(instance);
+
+    if !unrepresentable_properties.is_empty() || !unrepresentable_attributes.is_empty() {
+        debug!(
+            "{} has values JSON models can't hold ({}), falling back to a model file",
+            instance.name,
+            unrepresentable_properties
+                .iter()
+                .chain(unrepresentable_attributes.iter())
+                .cloned()
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        return None;
+    }
+
+    let mut children = Vec::new();
+    for child_id in instance.children() {
+        let child = tree.get_by_ref(*child_id).expect("got fake child id?");
+        if !left_out_of_model(child, has_scripts, excluded) {
+            children.push(build_model_json(
+                tree,
+                child,
+                remaining,
+                has_scripts,
+                excluded,
+            )?);
+        }
+    }
+
+    Some(ModelJson {
+        name: Some(instance.name.clone()),
+        class_name: instance.class.to_string(),
+        properties,
+        attributes,
+        children,
+    })
+}
+
+/// Builds a Rojo JSON model of `instance` and the descendants that belong in its model, or
+/// `None` if that's more than `limit` instances or holds values JSON can't represent.
+fn model_json(
+    tree: &WeakDom,
+    instance: &Instance,
+    limit: usize,
+    has_scripts: &HashMap<Ref, bool>,
+    excluded: &HashSet<Ref>,
+) -> Option<ModelJson> {
+    let mut remaining = limit;
+    build_model_json(tree, instance, &mut remaining, has_scripts, excluded)
+}
+
+/// The instruction writing `instance` and the descendants that belong in its model to
+/// `<stem>.<extension>` inside `folder`, as `.model.json` when it's small enough and in
+/// `model_format` otherwise. Descendants holding scripts are left for their own files, so
+/// every instance ends up in exactly one place.
+fn model_file<'a>(
+    tree: &WeakDom,
+    instance: &Instance,
+    folder: &Path,
+    stem: &str,
+    options: &ExportOptions,
+    has_scripts: &HashMap<Ref, bool>,
+    excluded: &HashSet<Ref>,
+) -> Option<Instruction<'a>> {
+    let json_model = options
+        .model_json_threshold
+        .and_then(|limit| model_json(tree, instance, limit, has_scripts, excluded));
+
+    if let Some(model) = json_model {
+        return Some(Instruction::CreateFile {
+            filename: Cow::Owned(folder.join(format!("{}.model.json", stem))),
+            contents: Cow::Owned(
+                serde_json::to_string_pretty(&model)
+                    .expect("couldn't serialize model")
+                    .into_bytes(),
+            ),
+        });
+    }
+
+    let model_bytes = serialize_model(tree, instance, options.model_format, has_scripts, excluded)?;
+    Some(Instruction::CreateFile {
+        filename: Cow::Owned(folder.join(format!("{}.{}", stem, options.model_format.extension()))),
+        contents: Cow::Owned(model_bytes),
+    })
+}
+
+/// The `default.project.json` that makes `folder` into `instance`: the model at `model`, with
+/// the instances written beside it, in `children`, as its children. Rojo builds a folder with
+/// a project file from that project alone, and it's the only way to give a model another name
+/// than its file's, or files of its own inside it.
+fn model_project<'a>(
+    tree: &WeakDom,
+    instance: &Instance,
+    folder: &Path,
+    model: &Path,
+    children: &[(&Instance, PathBuf)],
+) -> Instruction<'a> {
+    let relative = |path: &Path| {
+        path.strip_prefix(folder)
+            .unwrap_or(path)
+            .to_string_lossy()
+            .replace("\\", "/")
+    };
+
+    let mut node = serde_json::Map::new();
+    node.insert("$path".to_string(), json!(relative(model)));
+
+    for (child, path) in children {
+        // Children are named by their keys, so only one of them can have each name
+        let name = if node.contains_key(&child.name) {
+            let stem = &allocate_stems(tree, instance)[&child.referent()];
+            warn!(
+                "{} shares its name with a sibling, Rojo will name it {:?}",
+                full_name(tree, child),
+                stem
+            );
+            stem.clone()
+        } else {
+            child.name.clone()
+        };
+
+        node.insert(name, json!({ "$path": relative(path) }));
+    }
+
+    let project = json!({
+        "name": instance.name,
+        "tree": node,
+    });
+
+    Instruction::CreateFile {
+        filename: Cow::Owned(folder.join(PROJECT_FILE)),
+        contents: Cow::Owned(
+            serde_json::to_string_pretty(&project)
+                .expect("couldn't serialize project")
+                .into_bytes(),
+        ),
+    }
+}
+
+fn csv_field(field: &str) -> Cow<'_, str> {
+    if field.contains(|c| matches!(c, ',' | '"' | '\n' | '\r')) {
+        Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
+
+/// Converts a LocalizationTable's `Contents` into the CSV layout Rojo reads:
+/// `Key`, `Source`, `Context` and `Example` columns, followed by one column per locale.
+fn localization_csv(instance: &Instance) -> Option<String> {
+    let contents = match instance.properties.get(&ustr::ustr("Contents")) {
+        Some(Variant::String(contents)) => contents,
+        _ => return None,
+    };
+
+    let entries: Vec<LocalizationEntry> = match serde_json::from_str(contents) {
+        Ok(entries) => entries,
+        Err(error) => {
+            warn!("couldn't read the entries of {}: {}", instance.name, error);
+            return None;
+        }
+    };
+
+    let locales: BTreeSet<&str> = entries
+        .iter()
+        .flat_map(|entry| entry.values.keys().map(String::as_str))
+        .collect();
+
+    let mut csv = String::from("Key,Source,Context,Example");
+    for locale in &locales {
+        csv.push(',');
+        csv.push_str(&csv_field(locale));
+    }
+    csv.push('\n');
+
+    for entry in &entries {
+        let fields = [&entry.key, &entry.source, &entry.context, &entry.example];
+        let mut row: Vec<Cow<str>> = fields
+            .iter()
+            .map(|field| csv_field(field.as_deref().unwrap_or_default()))
+            .collect();
+
+        for locale in &locales {
+            row.push(csv_field(
+                entry
+                    .values
+                    .get(*locale)
+                    .map(String::as_str)
+                    .unwrap_or_default(),
+            ));
+        }
+
+        csv.push_str(&row.join(","));
+        csv.push('\n');
+    }
+
+    Some(csv)
+}
+
+/// Rojo has file types of its own for a few classes, which are far nicer to edit than models:
+/// StringValues become `.txt` files and LocalizationTables become `.csv` files.
+fn repr_native_file<'a>(
+    base: &'a Path,
+    child: &Instance,
+    stem: &str,
+) -> Option<Representation<'a>> {
+    let (extension, contents, represented_property) = match child.class.as_str() {
+        "StringValue" => {
+            let value = match child.properties.get(&ustr::ustr("Value")) {
+                Some(Variant::String(value)) => value.clone(),
+                None => String::new(),
+                Some(_) => return None,
+            };
+
+            ("txt", value.into_bytes(), "Value")
+        }
+
+        "LocalizationTable" => ("csv", localization_csv(child)?.into_bytes(), "Contents"),
+
+        _ => return None,
+    };
+
+    let mut meta = instance_meta(child, stem);
+    meta.properties.remove(represented_property);
+
+    let mut instructions = vec![Instruction::CreateFile {
+        filename: Cow::Owned(base.join(format!("{}.{}", stem, extension))),
+        contents: Cow::Owned(contents),
+    }];
+
+    if !meta.is_empty() {
+        instructions.push(Instruction::CreateFile {
+            filename: Cow::Owned(base.join(format!("{}.meta.json", stem))),
+            contents: Cow::Owned(
+                serde_json::to_string_pretty(&meta)
+                    .expect("couldn't serialize meta")
+                    .into_bytes(),
+            ),
+        });
+    }
+
+    Some(Representation {
+        instructions,
+        path: Cow::Borrowed(base),
+        traversal: ChildTraversal::Skip,
+        model: None,
+        project: false,
+        notes: Vec::new(),
+    })
+}
+
 fn repr_instance<'a>(
     tree: &'a WeakDom,
     base: &'a Path,
     child: &'a Instance,
+    stem: &str,
     has_scripts: &'a HashMap<Ref, bool>,
-    mode: ExportMode,
-) -> Option<Representation<'a>> {
+    excluded: &HashSet<Ref>,
+    options: &ExportOptions,
+) -> Result<Representation<'a>, SkipReason> {
+    let mode = options.mode;
     let contains_scripts = has_scripts.get(&child.referent()).copied().unwrap_or(false);
 
+    if matches!(mode, ExportMode::Full) && child.children().is_empty() {
+        if let Some(representation) = repr_native_file(base, child, stem) {
+            return Ok(representation);
+        }
+    }
+
     match child.class.as_str() {
         "Folder" => {
             if matches!(mode, ExportMode::ScriptsOnly) && !contains_scripts {
-                return None;
+                return Err(SkipReason::NoScripts);
             }
 
-            let folder_path = sanitized_join(base, &child.name);
+            let folder_path = base.join(stem);
             let owned: Cow<'a, Path> = Cow::Owned(folder_path);
             let clone = owned.clone();
-            Some(Representation {
+            Ok(Representation {
                 instructions: vec![
                     Instruction::CreateFolder { folder: clone },
                     Instruction::CreateFile {
                         filename: Cow::Owned(owned.join("init.meta.json")),
                         contents: Cow::Owned(
-                            serde_json::to_string_pretty(&MetaFile {
-                                class_name: None,
-                                // properties: BTreeMap::new(),
-                                ignore_unknown_instances: true,
-                            })
-                            .unwrap()
-                            .as_bytes()
-                            .into(),
+                            serde_json::to_string_pretty(&instance_meta(child, stem))
+                                .unwrap()
+                                .as_bytes()
+                                .into(),
                         ),
                     },
                 ],
                 path: owned,
                 traversal: ChildTraversal::Normal,
+                model: None,
+                project: false,
+                notes: Vec::new(),
             })
         }
 
         "Script" | "LocalScript" | "ModuleScript" => {
-            let extension = match child.class.as_str() {
-                "Script" => ".server.luau",
-                "LocalScript" => ".client.luau",
-                "ModuleScript" => ".luau",
-                _ => unreachable!(),
-            };
-
+            let mut notes = Vec::new();
             let source = match child.properties.get(&ustr::ustr("Source")) {
                 Some(Variant::String(value)) => value.as_bytes(),
                 Some(other) => {
-                    warn!("unexpected Source variant for {} ( {:?} ), writing empty file", child.name, other);
+                    warn!(
+                        "unexpected Source variant for {} ( {:?} ), writing empty file",
+                        child.name, other
+                    );
+                    notes.push(format!(
+                        "Source is {:?} rather than a string, wrote an empty script",
+                        other.ty()
+                    ));
                     &[]
                 }
                 None => {
@@ -208,122 +826,108 @@ fn repr_instance<'a>(
                         "missing Source on {} ({}), writing empty file",
                         child.name, child.class
                     );
+                    notes.push("has no Source, wrote an empty script".to_string());
                     &[]
                 }
             };
 
+            let meta = instance_meta(child, stem);
+
             if child.children().is_empty() {
-                Some(Representation {
-                    instructions: vec![Instruction::CreateFile {
-                        filename: Cow::Owned(sanitized_join(
-                            base,
-                            &format!("{}{}", child.name, extension),
-                        )),
-                        contents: Cow::Borrowed(source),
-                    }],
+                let mut instructions = vec![Instruction::CreateFile {
+                    filename: Cow::Owned(base.join(script_file_name(
+                        &child.class,
+                        stem,
+                        options.script_extension,
+                    ))),
+                    contents: Cow::Borrowed(source),
+                }];
+
+                if !meta.is_empty() {
+                    instructions.push(Instruction::CreateFile {
+                        filename: Cow::Owned(base.join(format!("{}.meta.json", stem))),
+                        contents: Cow::Owned(
+                            serde_json::to_string_pretty(&meta)
+                                .expect("couldn't serialize meta")
+                                .as_bytes()
+                                .into(),
+                        ),
+                    });
+                }
+
+                Ok(Representation {
+                    instructions,
                     path: Cow::Borrowed(base),
                     traversal: ChildTraversal::Skip,
+                    model: None,
+                    project: false,
+                    notes,
                 })
             } else {
-                let meta_contents = Cow::Owned(
-                    serde_json::to_string_pretty(&MetaFile {
-                        class_name: None,
-                        // properties: BTreeMap::new(),
-                        ignore_unknown_instances: true,
-                    })
-                    .expect("couldn't serialize meta")
-                    .as_bytes()
-                    .into(),
-                );
-
-                let script_children_count = child
+                let only_script_children = child
                     .children()
                     .iter()
-                    .filter(|id| has_scripts.get(id) == Some(&true))
-                    .count();
-
-                let total_children_count = child.children().len();
-                let folder_path: Cow<'a, Path> = Cow::Owned(sanitized_join(base, &child.name));
+                    .filter(|id| !excluded.contains(id))
+                    .all(|id| has_scripts.get(id) == Some(&true));
 
                 // Any script with children becomes a folder so its descendants stay nested
-                match script_children_count {
-                    _ if script_children_count == total_children_count => Some(Representation {
-                        instructions: vec![
-                            Instruction::CreateFolder {
-                                folder: folder_path.clone(),
-                            },
-                            Instruction::CreateFile {
-                                filename: Cow::Owned(
-                                    folder_path.join(format!("init{}.lua", extension)),
-                                ),
-                                contents: Cow::Borrowed(source),
-                            },
-                        ],
-                        path: folder_path,
-                        traversal: ChildTraversal::Normal,
-                    }),
-
-                    0 => Some(Representation {
-                        instructions: vec![
-                            Instruction::CreateFolder {
-                                folder: folder_path.clone(),
-                            },
-                            Instruction::CreateFile {
-                                filename: Cow::Owned(
-                                    folder_path.join(format!("init{}.lua", extension)),
-                                ),
-                                contents: Cow::Borrowed(source),
-                            },
-                            Instruction::CreateFile {
-                                filename: Cow::Owned(folder_path.join("init.meta.json")),
-                                contents: meta_contents,
-                            },
-                        ],
-                        path: folder_path,
-                        traversal: ChildTraversal::Normal,
-                    }),
-
-                    _ => Some(Representation {
-                        instructions: vec![
-                            Instruction::CreateFolder {
-                                folder: folder_path.clone(),
-                            },
-                            Instruction::CreateFile {
-                                filename: Cow::Owned(
-                                    folder_path.join(format!("init{}.lua", extension)),
-                                ),
-                                contents: Cow::Borrowed(source),
-                            },
-                            Instruction::CreateFile {
-                                filename: Cow::Owned(folder_path.join("init.meta.json")),
-                                contents: meta_contents,
-                            },
-                        ],
-                        path: folder_path,
-                        traversal: ChildTraversal::Normal,
-                    }),
+                let folder_path: Cow<'a, Path> = Cow::Owned(base.join(stem));
+                let mut instructions = vec![
+                    Instruction::CreateFolder {
+                        folder: folder_path.clone(),
+                    },
+                    Instruction::CreateFile {
+                        filename: Cow::Owned(folder_path.join(script_file_name(
+                            &child.class,
+                            "init",
+                            options.script_extension,
+                        ))),
+                        contents: Cow::Borrowed(source),
+                    },
+                ];
+
+                if !only_script_children || !meta.is_empty() {
+                    instructions.push(Instruction::CreateFile {
+                        filename: Cow::Owned(folder_path.join("init.meta.json")),
+                        contents: Cow::Owned(
+                            serde_json::to_string_pretty(&meta)
+                                .expect("couldn't serialize meta")
+                                .as_bytes()
+                                .into(),
+                        ),
+                    });
                 }
+
+                Ok(Representation {
+                    instructions,
+                    path: folder_path,
+                    traversal: ChildTraversal::Normal,
+                    model: None,
+                    project: false,
+                    notes,
+                })
             }
         }
 
         other_class => {
+            let mut notes = Vec::new();
             let db = rbx_reflection_database::get().expect("couldn't get reflection database");
             match db.classes.get(other_class) {
                 Some(reflected) => {
                     let is_service = reflected.tags.contains(&ClassTag::Service);
                     if is_service {
                         if matches!(mode, ExportMode::ScriptsOnly) && !contains_scripts {
-                            return None;
+                            return Err(SkipReason::NoScripts);
                         }
 
-                        if !RESPECTED_SERVICES.contains(other_class) {
-                            return None;
+                        if !options.respected_services.contains(other_class) {
+                            return Err(SkipReason::IgnoredService);
                         }
 
-                        let new_base: Cow<'a, Path> = Cow::Owned(sanitized_join(base, &child.name));
+                        let new_base: Cow<'a, Path> = Cow::Owned(base.join(stem));
                         let mut instructions = Vec::new();
 
-                        if !NON_TREE_SERVICES.contains(other_class) {
+                        if !options.non_tree_services.contains(other_class) {
                             instructions
                                 .push(Instruction::add_to_tree(&child, new_base.to_path_buf()));
                         }
@@ -332,152 +936,700 @@ fn repr_instance<'a>(
                             folder: new_base.clone(),
                         });
 
-                        return Some(Representation {
+                        return Ok(Representation {
                             instructions,
                             path: new_base,
                             traversal: ChildTraversal::Normal,
+                            model: None,
+                            project: false,
+                            notes,
                         });
                     }
                 }
 
                 None => {
                     debug!("class is not in reflection? {}", other_class);
+                    notes.push("class isn't in the reflection database".to_string());
                 }
             }
 
             if matches!(mode, ExportMode::ScriptsOnly) && !contains_scripts {
-                return None;
+                return Err(SkipReason::NoScripts);
+            }
+
+            if !contains_scripts {
+                // Nothing below needs a file of its own, so the whole subtree is one model.
+                // Rojo names a model after its file, so one that had to be renamed goes in a
+                // folder whose project file gives it its real name.
+                let renamed = stem != child.name;
+                let model_folder = if renamed {
+                    base.join(stem)
+                } else {
+                    base.to_path_buf()
+                };
+
+                let mut instructions = Vec::new();
+                if renamed {
+                    instructions.push(Instruction::CreateFolder {
+                        folder: Cow::Owned(model_folder.clone()),
+                    });
+                }
+
+                let model_instruction = model_file(
+                    tree,
+                    child,
+                    &model_folder,
+                    stem,
+                    options,
+                    has_scripts,
+                    excluded,
+                )
+                .ok_or(SkipReason::SerializationFailed)?;
+
+                let model_path = match &model_instruction {
+                    Instruction::CreateFile { filename, .. } => filename.to_path_buf(),
+                    _ => unreachable!("model_file only creates files"),
+                };
+                instructions.push(model_instruction);
+
+                return Ok(Representation {
+                    instructions,
+                    path: Cow::Owned(model_folder),
+                    traversal: ChildTraversal::Packed,
+                    model: Some(model_path),
+                    project: renamed,
+                    notes,
+                });
             }
 
-            let folder_path: Cow<'a, Path> = Cow::Owned(sanitized_join(base, &child.name));
+            let folder_path: Cow<'a, Path> = Cow::Owned(base.join(stem));
 
             let mut instructions = vec![Instruction::CreateFolder {
                 folder: folder_path.clone(),
             }];
+            let mut model = None;
 
-            let traversal = if contains_scripts {
-                if matches!(mode, ExportMode::ScriptsOnly) {
-                    ChildTraversal::ScriptsOnly
-                } else {
-                    let model_bytes = serialize_instance_to_rbxm(tree, child)?;
+            let traversal = if matches!(mode, ExportMode::ScriptsOnly) {
+                // Rojo names the folder's instance after the folder, so a renamed instance
+                // needs its real name in a meta file
+                if stem != child.name {
                     instructions.push(Instruction::CreateFile {
-                        filename: Cow::Owned(folder_path.join("init.rbxmx")),
-                        contents: Cow::Owned(model_bytes),
+                        filename: Cow::Owned(folder_path.join("init.meta.json")),
+                        contents: Cow::Owned(
+                            serde_json::to_string_pretty(&MetaFile {
+                                name: Some(child.name.clone()),
+                                ..MetaFile::default()
+                            })
+                            .expect("couldn't serialize meta")
+                            .as_bytes()
+                            .into(),
+                        ),
                     });
-                    ChildTraversal::ScriptsOnly
                 }
+
+                ChildTraversal::ScriptsOnly
+            } else if let Some(meta) = options
+                .containers_as_folders
+                .then(|| container_meta(child, stem))
+                .flatten()
+            {
+                // Everything inside is written on its own, like in any other folder
+                instructions.push(Instruction::CreateFile {
+                    filename: Cow::Owned(folder_path.join("init.meta.json")),
+                    contents: Cow::Owned(
+                        serde_json::to_string_pretty(&meta)
+                            .expect("couldn't serialize meta")
+                            .as_bytes()
+                            .into(),
+                    ),
+                });
+
+                ChildTraversal::Normal
             } else {
-                ChildTraversal::Skip
+                // The model holds everything but the scripts, and whatever holds them, which
+                // are written as files beside it. Rojo doesn't read a model as its folder's
+                // instance, so the folder's project file puts the two together.
+                let model_instruction = model_file(
+                    tree,
+                    child,
+                    &folder_path,
+                    stem,
+                    options,
+                    has_scripts,
+                    excluded,
+                )
+                .ok_or(SkipReason::SerializationFailed)?;
+
+                if let Instruction::CreateFile { filename, .. } = &model_instruction {
+                    model = Some(filename.to_path_buf());
+                }
+
+                instructions.push(model_instruction);
+                ChildTraversal::ScriptsOnly
             };
 
-            Some(Representation {
+            Ok(Representation {
                 instructions,
                 path: folder_path,
                 traversal,
+                project: model.is_some(),
+                model,
+                notes,
             })
         }
     }
 }
 
+/// Sits in front of the real reader and warns about any emitted paths that would land on the
+/// same file on a case-insensitive filesystem. Stems are already deduplicated per parent, so
+/// this only fires if two representations still write to overlapping paths.
+struct CollisionDetector<'a> {
+    reader: &'a mut dyn InstructionReader,
+    seen: HashMap<String, (PathBuf, bool)>,
+    collisions: Vec<Collision>,
+}
+
+impl InstructionReader for CollisionDetector<'_> {
+    fn read_instruction<'b>(&mut self, instruction: Instruction<'b>) -> Result<(), Error> {
+        let (path, is_folder): (Option<&Path>, bool) = match &instruction {
+            Instruction::CreateFile { filename, .. } => (Some(&**filename), false),
+            Instruction::CreateFolder { folder } => (Some(&**folder), true),
+            Instruction::AddToTree { .. }
+            | Instruction::SetTreeRoot { .. }
+            | Instruction::ConfigureProject { .. } => (None, false),
+        };
+
+        if let Some(path) = path {
+            let key = path.to_string_lossy().replace("\\", "/").to_lowercase();
+
+            match self.seen.get(&key) {
+                // Recreating the exact same folder is harmless
+                Some((previous, true)) if is_folder && previous.as_path() == path => {}
+
+                Some((previous, _)) => {
+                    warn!(
+                        "{} collides with {} on case-insensitive filesystems, one will overwrite the other",
+                        path.display(),
+                        previous.display()
+                    );
+
+                    self.collisions.push(Collision {
+                        path: path.to_path_buf(),
+                        existing: previous.clone(),
+                    });
+                }
+
+                None => {
+                    self.seen.insert(key, (path.to_path_buf(), is_folder));
+                }
+            }
+        }
+
+        self.reader.read_instruction(instruction)
+    }
+
+    fn finish_instructions(&mut self) -> Result<(), Error> {
+        self.reader.finish_instructions()
+    }
+}
+
 impl<'a, I: InstructionReader + ?Sized> TreeIterator<'a, I> {
+    fn record(
+        &mut self,
+        instance: &Instance,
+        decision: Decision,
+        reason: Option<String>,
+        path: Option<PathBuf>,
+    ) {
+        self.report.instances.push(InstanceReport {
+            instance: full_name(self.tree, instance),
+            referent: instance.referent(),
+            class_name: instance.class.to_string(),
+            decision,
+            reason,
+            path,
+        });
+    }
+
+    fn skip(&mut self, instance: &Instance, reason: SkipReason) {
+        debug!("skipping {}: {}", full_name(self.tree, instance), reason);
+        self.record(instance, Decision::Skipped, Some(reason.to_string()), None);
+    }
+
+    /// Records everything below an instance that was written without its descendants.
+    fn skip_descendants(&mut self, instance: &Instance) {
+        for child_id in instance.children() {
+            let child = self.tree.get_by_ref(*child_id).expect("got fake child id?");
+
+            if self.excluded.contains(child_id) {
+                self.skip(child, SkipReason::Filtered);
+            } else {
+                self.skip(child, SkipReason::AncestorNotTraversed);
+                self.skip_descendants(child);
+            }
+        }
+    }
+
+    /// Records the descendants of an instance that were packed into its model. Scripts and
+    /// whatever holds them aren't part of the model and are visited on their own.
+    fn pack_descendants(
+        &mut self,
+        instance: &Instance,
+        model: &Path,
+        has_scripts: &HashMap<Ref, bool>,
+    ) {
+        for child_id in instance.children() {
+            let child = self.tree.get_by_ref(*child_id).expect("got fake child id?");
+
+            if self.excluded.contains(child_id) {
+                self.skip(child, SkipReason::Filtered);
+            } else if !left_out_of_model(child, has_scripts, self.excluded) {
+                self.record(child, Decision::Packed, None, Some(model.to_path_buf()));
+                self.pack_descendants(child, model, has_scripts);
+            }
+        }
+    }
+
+    /// Writes the children of an instance, returning the ones that were written and where.
     fn visit_instructions(
         &mut self,
         instance: &Instance,
         has_scripts: &HashMap<Ref, bool>,
         scripts_only: bool,
-    ) {
+    ) -> Result<Vec<(&'a Instance, PathBuf)>, Error> {
+        let stems = allocate_stems(self.tree, instance);
+        let mut written = Vec::new();
+
         for child_id in instance.children() {
             let child = self.tree.get_by_ref(*child_id).expect("got fake child id?");
+            let stem = &stems[child_id];
+            if let Some(path) = self.visit_child(child, stem, has_scripts, scripts_only)? {
+                written.push((child, path));
+            }
+        }
+
+        Ok(written)
+    }
 
-            if matches!(self.mode, ExportMode::ScriptsOnly) && !has_scripts.get(child_id).copied().unwrap_or(false) {
-                continue;
+    /// Writes one instance under this iterator's path, along with whatever of its descendants
+    /// it carries. Returns where it was written, or `None` if it was skipped.
+    fn visit_child(
+        &mut self,
+        child: &'a Instance,
+        stem: &str,
+        has_scripts: &HashMap<Ref, bool>,
+        scripts_only: bool,
+    ) -> Result<Option<PathBuf>, Error> {
+        if self.excluded.contains(&child.referent()) {
+            self.skip(child, SkipReason::Filtered);
+            return Ok(None);
+        }
+
+        if matches!(self.options.mode, ExportMode::ScriptsOnly)
+            && !has_scripts.get(&child.referent()).copied().unwrap_or(false)
+        {
+            self.skip(child, SkipReason::NoScripts);
+            return Ok(None);
+        }
+
+        if scripts_only && !is_script_class(child.class.as_str()) {
+            // Whatever holds no scripts is already in the parent's model
+            if !*has_scripts.get(&child.referent()).unwrap_or(&false) {
+                return Ok(None);
             }
 
-            if scripts_only && !is_script_class(child.class.as_str()) {
-                if *has_scripts.get(child_id).unwrap_or(&false) {
-                    let next_path = sanitized_join(self.path, &child.name);
+            // Without models, only the folders leading to the scripts are written. With them,
+            // the instance is written below like any other, with a model of its own.
+            if matches!(self.options.mode, ExportMode::ScriptsOnly) {
+                let next_path = self.path.join(stem);
 
-                    TreeIterator {
-                        instruction_reader: self.instruction_reader,
-                        path: next_path.as_path(),
-                        tree: self.tree,
-                        mode: self.mode,
-                    }
-                    .visit_instructions(child, has_scripts, true);
+                self.record(
+                    child,
+                    Decision::Exported,
+                    Some("only kept as the folder holding its scripts".to_string()),
+                    Some(next_path.clone()),
+                );
+
+                TreeIterator {
+                    instruction_reader: self.instruction_reader,
+                    path: next_path.as_path(),
+                    tree: self.tree,
+                    options: self.options,
+                    report: self.report,
+                    excluded: self.excluded,
                 }
+                .visit_instructions(child, has_scripts, true)?;
 
-                continue;
+                return Ok(Some(next_path));
             }
+        }
 
-            if should_skip_service(child.class.as_str()) {
-                continue;
+        if should_skip_service(child.class.as_str(), &self.options.respected_services) {
+            if !self
+                .options
+                .settings_services
+                .contains(child.class.as_str())
+            {
+                self.skip(child, SkipReason::IgnoredService);
+                return Ok(None);
             }
 
-            let representation = if child.class == "StarterPlayer" {
-                let folder_path: Cow<'a, Path> = Cow::Owned(sanitized_join(self.path, &child.name));
-                let mut instructions = Vec::new();
-
-                instructions.push(Instruction::CreateFolder {
-                    folder: folder_path.clone(),
-                });
+            // Its contents aren't exported, but its settings are still kept in the project tree
+            let node = Instruction::node(child);
+            if node.properties.is_empty() && node.attributes.is_empty() {
+                self.skip(child, SkipReason::IgnoredService);
+                return Ok(None);
+            }
 
-                instructions.push(Instruction::AddToTree {
+            self.instruction_reader
+                .read_instruction(Instruction::AddToTree {
                     name: child.name.to_string(),
-                    partition: TreePartition {
-                        class_name: child.class.to_string(),
-                        children: child
-                            .children()
-                            .iter()
-                            .map(|child_id| {
-                                let child = self.tree.get_by_ref(*child_id).unwrap();
-                                (
-                                    child.name.to_string(),
-                                    Instruction::partition(
-                                        &child,
-                                        sanitized_join(folder_path.as_ref(), child.name.as_str()),
-                                    ),
-                                )
-                            })
-                            .collect(),
-                        ignore_unknown_instances: true,
-                        path: None,
-                    },
-                });
-
-                Some(Representation {
-                    instructions,
-                    path: folder_path,
-                    traversal: ChildTraversal::Normal,
+                    partition: node,
                 })
-            } else {
-                repr_instance(self.tree, self.path, child, has_scripts, self.mode)
-            };
+                .map_err(|error| error.with_instance(full_name(self.tree, child)))?;
+
+            self.record(
+                child,
+                Decision::Exported,
+                Some("only its properties are kept, the service isn't exported".to_string()),
+                None,
+            );
+            self.skip_descendants(child);
+            return Ok(None);
+        }
 
-            let Some(representation) = representation else {
-                continue;
-            };
+        let rename_reason = if stem != sanitize_component(&child.name) {
+            warn!(
+                "{} shares its name with a sibling, writing it as {:?}",
+                full_name(self.tree, child),
+                stem
+            );
+            Some("shares its name with a sibling")
+        } else if stem != child.name {
+            info!(
+                "{} isn't a valid file name, writing it as {:?}",
+                full_name(self.tree, child),
+                stem
+            );
+            Some("isn't a valid file name")
+        } else {
+            None
+        };
+
+        let representation = if child.class == "StarterPlayer" {
+            let folder_path: Cow<'a, Path> = Cow::Owned(self.path.join(stem));
+            let child_stems = allocate_stems(self.tree, child);
+            let mut instructions = Vec::new();
 
-            let Representation {
+            instructions.push(Instruction::CreateFolder {
+                folder: folder_path.clone(),
+            });
+
+            instructions.push(Instruction::AddToTree {
+                name: child.name.to_string(),
+                partition: TreePartition {
+                    class_name: child.class.to_string(),
+                    children: child
+                        .children()
+                        .iter()
+                        .filter(|child_id| !self.excluded.contains(child_id))
+                        .map(|child_id| {
+                            let child = self.tree.get_by_ref(*child_id).unwrap();
+                            (
+                                child.name.to_string(),
+                                Instruction::partition(
+                                    &child,
+                                    folder_path.join(&child_stems[child_id]),
+                                ),
+                            )
+                        })
+                        .collect(),
+                    ignore_unknown_instances: true,
+                    path: None,
+                    properties: instance_properties(child),
+                    attributes: instance_attributes(child),
+                },
+            });
+
+            Ok(Representation {
                 instructions,
-                path,
-                traversal,
-            } = representation;
+                path: folder_path,
+                traversal: ChildTraversal::Normal,
+                model: None,
+                project: false,
+                notes: Vec::new(),
+            })
+        } else {
+            repr_instance(
+                self.tree,
+                self.path,
+                child,
+                stem,
+                has_scripts,
+                self.excluded,
+                self.options,
+            )
+        };
+
+        let representation = match representation {
+            Ok(representation) => representation,
+            Err(reason) => {
+                self.skip(child, reason);
+                return Ok(None);
+            }
+        };
+
+        let Representation {
+            instructions,
+            path,
+            traversal,
+            model,
+            project,
+            notes,
+        } = representation;
+
+        let written_path = instructions
+            .iter()
+            .find_map(|instruction| match instruction {
+                Instruction::CreateFile { filename, .. } => Some(filename.to_path_buf()),
+                Instruction::CreateFolder { folder } => Some(folder.to_path_buf()),
+                Instruction::AddToTree { .. }
+                | Instruction::SetTreeRoot { .. }
+                | Instruction::ConfigureProject { .. } => None,
+            })
+            .unwrap_or_else(|| path.to_path_buf());
+
+        let reason = if notes.is_empty() {
+            None
+        } else {
+            Some(notes.join("; "))
+        };
+
+        match &model {
+            Some(model) => {
+                self.record(child, Decision::Packed, reason, Some(model.clone()));
+                self.pack_descendants(child, model, has_scripts);
+            }
+
+            None => self.record(
+                child,
+                Decision::Exported,
+                reason,
+                Some(written_path.clone()),
+            ),
+        }
+
+        if let Some(rename_reason) = rename_reason {
+            self.record(
+                child,
+                Decision::Renamed,
+                Some(rename_reason.to_string()),
+                Some(written_path.clone()),
+            );
+        }
+
+        self.instruction_reader
+            .read_instructions(instructions)
+            .map_err(|error| error.with_instance(full_name(self.tree, child)))?;
+
+        let mut iterator = TreeIterator {
+            instruction_reader: self.instruction_reader,
+            path: path.as_ref(),
+            tree: self.tree,
+            options: self.options,
+            report: self.report,
+            excluded: self.excluded,
+        };
+
+        let children = match traversal {
+            // Nothing below a folder or a script is in a model, so in full mode all of it is written
+            ChildTraversal::Normal => iterator.visit_instructions(
+                child,
+                has_scripts,
+                scripts_only && matches!(self.options.mode, ExportMode::ScriptsOnly),
+            )?,
+            ChildTraversal::ScriptsOnly => iterator.visit_instructions(child, has_scripts, true)?,
+            ChildTraversal::Skip => {
+                iterator.skip_descendants(child);
+                Vec::new()
+            }
+            ChildTraversal::Packed => Vec::new(),
+        };
+
+        if project {
+            let model = model
+                .as_ref()
+                .expect("only models get a project of their own");
+            self.instruction_reader
+                .read_instruction(model_project(self.tree, child, &path, model, &children))
+                .map_err(|error| error.with_instance(full_name(self.tree, child)))?;
+        }
+
+        Ok(Some(written_path))
+    }
+
+    /// Records what's left out of a subtree export: everything that isn't selected and doesn't
+    /// lead to something that is. The instances leading to a selection are recorded as kept.
+    fn skip_unselected(
+        &mut self,
+        instance: &Instance,
+        selected: &HashSet<Ref>,
+        leading: &HashSet<Ref>,
+    ) {
+        for child_id in instance.children() {
+            let child = self.tree.get_by_ref(*child_id).expect("got fake child id?");
+
+            if leading.contains(child_id) {
+                self.record(
+                    child,
+                    Decision::Exported,
+                    Some("only kept as a node of the project tree".to_string()),
+                    None,
+                );
+                self.skip_unselected(child, selected, leading);
+            } else if !selected.contains(child_id) {
+                self.skip(child, SkipReason::NotSelected);
+            }
+        }
+    }
+
+    /// Exports only `subtrees` and their descendants, each to the path it would have in a
+    /// full export. Their ancestors become nodes of the project tree without any files, so
+    /// every subtree still ends up in the right place when the project is synced.
+    fn visit_subtrees(
+        &mut self,
+        subtrees: &[&'a Instance],
+        has_scripts: &HashMap<Ref, bool>,
+    ) -> Result<(), Error> {
+        let selected: HashSet<Ref> = subtrees.iter().map(|subtree| subtree.referent()).collect();
+
+        // A subtree inside another one is already exported with it
+        let mut seen = HashSet::new();
+        let chains: Vec<Vec<&'a Instance>> = subtrees
+            .iter()
+            .filter(|subtree| seen.insert(subtree.referent()))
+            .map(|subtree| ancestry(self.tree, *subtree))
+            .filter(|chain| {
+                chain[..chain.len() - 1]
+                    .iter()
+                    .all(|ancestor| !selected.contains(&ancestor.referent()))
+            })
+            .collect();
+
+        let leading: HashSet<Ref> = chains
+            .iter()
+            .flat_map(|chain| chain[..chain.len() - 1].iter())
+            .map(|ancestor| ancestor.referent())
+            .collect();
+
+        self.skip_unselected(self.tree.root(), &selected, &leading);
+
+        let mut nodes = BTreeMap::new();
+        let mut has_services = false;
 
-            self.instruction_reader.read_instructions(instructions);
+        for chain in &chains {
+            let subtree = chain[chain.len() - 1];
+            let mut base = PathBuf::new();
+            let mut parent = self.tree.root();
 
-            let mut iterator = TreeIterator {
-                instruction_reader: self.instruction_reader,
-                path: path.as_ref(),
+            for ancestor in &chain[..chain.len() - 1] {
+                base.push(&allocate_stems(self.tree, parent)[&ancestor.referent()]);
+                parent = *ancestor;
+            }
+
+            let stem = allocate_stems(self.tree, parent)[&subtree.referent()].clone();
+
+            let written = TreeIterator {
+                instruction_reader: &mut *self.instruction_reader,
+                path: &base,
                 tree: self.tree,
-                mode: self.mode,
+                options: self.options,
+                report: &mut *self.report,
+                excluded: self.excluded,
+            }
+            .visit_child(subtree, &stem, has_scripts, false)?;
+
+            if is_service(&chain[0].class) {
+                has_services = true;
+
+                // Services add themselves to the tree
+                if chain.len() == 1 {
+                    continue;
+                }
+            }
+
+            let path = match written {
+                Some(path) => path,
+                None => continue,
             };
 
-            match traversal {
-                ChildTraversal::Normal => iterator.visit_instructions(child, has_scripts, scripts_only),
-                ChildTraversal::ScriptsOnly => iterator.visit_instructions(child, has_scripts, true),
-                ChildTraversal::Skip => {}
+            let mut name = subtree.name.clone();
+            let mut node = TreePartition {
+                class_name: subtree.class.to_string(),
+                children: BTreeMap::new(),
+                ignore_unknown_instances: true,
+                path: Some(path),
+                properties: Properties::new(),
+                attributes: Properties::new(),
+            };
+
+            for ancestor in chain[..chain.len() - 1].iter().rev() {
+                let mut children = BTreeMap::new();
+                children.insert(name, node);
+
+                node = TreePartition {
+                    children,
+                    ..Instruction::node(ancestor)
+                };
+                name = ancestor.name.clone();
             }
+
+            merge_partition(&mut nodes, name, node);
+        }
+
+        let (services, mut models): (Vec<_>, Vec<_>) = nodes
+            .into_iter()
+            .partition(|(_, partition)| is_service(&partition.class_name));
+
+        let mut instructions: Vec<Instruction> = services
+            .into_iter()
+            .map(|(name, partition)| Instruction::AddToTree { name, partition })
+            .collect();
+
+        // Anything that isn't a service is the project's tree when it's on its own. Next to
+        // services, it goes under the place's DataModel, and next to other models, under a
+        // Folder standing in for the model file they came from.
+        if has_services {
+            instructions.extend(
+                models
+                    .into_iter()
+                    .map(|(name, partition)| Instruction::AddToTree { name, partition }),
+            );
+        } else if models.len() == 1 {
+            let (name, partition) = models.remove(0);
+            instructions.push(Instruction::SetTreeRoot { name, partition });
+        } else if !models.is_empty() {
+            let name = self
+                .options
+                .project
+                .name
+                .clone()
+                .unwrap_or_else(|| "project".to_string());
+
+            instructions.push(Instruction::SetTreeRoot {
+                name,
+                partition: TreePartition {
+                    class_name: "Folder".to_string(),
+                    children: models.into_iter().collect(),
+                    ignore_unknown_instances: true,
+                    path: None,
+                    properties: Properties::new(),
+                    attributes: Properties::new(),
+                },
+            });
         }
+
+        self.instruction_reader.read_instructions(instructions)
     }
 }
 
@@ -485,14 +1637,21 @@ fn check_has_scripts(
     tree: &WeakDom,
     instance: &Instance,
     has_scripts: &mut HashMap<Ref, bool>,
+    excluded: &HashSet<Ref>,
 ) -> bool {
     let mut children_have_scripts = false;
 
     for child_id in instance.children() {
+        // Scripts that are filtered out don't need anything kept around them
+        if excluded.contains(child_id) {
+            continue;
+        }
+
         let result = check_has_scripts(
             tree,
             tree.get_by_ref(*child_id).expect("fake child id?"),
             has_scripts,
+            excluded,
         );
 
         children_have_scripts = children_have_scripts || result;
@@ -510,22 +1669,54 @@ fn check_has_scripts(
 pub fn process_instructions(
     tree: &WeakDom,
     instruction_reader: &mut dyn InstructionReader,
-    mode: ExportMode,
-) {
+    options: &ExportOptions,
+) -> Result<Report, Error> {
     let root = tree.root_ref();
     let root_instance = tree.get_by_ref(root).expect("fake root id?");
     let path = PathBuf::new();
 
+    let excluded = options.filters.excluded_instances(tree);
+
     let mut has_scripts = HashMap::new();
-    check_has_scripts(tree, root_instance, &mut has_scripts);
+    check_has_scripts(tree, root_instance, &mut has_scripts, &excluded);
+
+    let mut collision_detector = CollisionDetector {
+        reader: instruction_reader,
+        seen: HashMap::new(),
+        collisions: Vec::new(),
+    };
+
+    let subtrees = options
+        .subtrees
+        .iter()
+        .map(|subtree| {
+            find_by_path(tree, subtree).ok_or_else(|| Error::instance_not_found(subtree))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
 
-    TreeIterator {
-        instruction_reader,
+    collision_detector.read_instruction(Instruction::ConfigureProject {
+        options: options.project.clone(),
+    })?;
+
+    let mut report = Report::default();
+
+    let mut iterator = TreeIterator {
+        instruction_reader: &mut collision_detector,
         path: &path,
         tree,
-        mode,
+        options,
+        report: &mut report,
+        excluded: &excluded,
+    };
+
+    if subtrees.is_empty() {
+        iterator.visit_instructions(&root_instance, &has_scripts, false)?;
+    } else {
+        iterator.visit_subtrees(&subtrees, &has_scripts)?;
     }
-    .visit_instructions(&root_instance, &has_scripts, false);
 
-    instruction_reader.finish_instructions();
+    collision_detector.finish_instructions()?;
+    report.collisions = collision_detector.collisions;
+
+    Ok(report)
 }
@@ -0,0 +1,814 @@
+use log::{debug, warn};
+use rbx_dom_weak::{
+    types::{
+        Attributes, Axes, BrickColor, CFrame, Color3, Color3uint8, ColorSequence,
+        ColorSequenceKeypoint, Content, ContentId, CustomPhysicalProperties, Enum, Faces, Font,
+        FontStyle, FontWeight, Matrix3, NumberRange, NumberSequence, NumberSequenceKeypoint,
+        PhysicalProperties, Rect, Tags, UDim, UDim2, Variant, VariantType, Vector2, Vector2int16,
+        Vector3, Vector3int16,
+    },
+    Instance,
+};
+use rbx_reflection::{
+    ClassDescriptor, PropertyDescriptor, PropertyKind, PropertySerialization, Scriptability,
+};
+use serde_json::{json, Value};
+use std::{borrow::Cow, collections::BTreeMap, fmt};
+
+pub type Properties = BTreeMap<String, Value>;
+
+// Properties that are either expressed by the file layout itself or handled separately
+const IGNORED_PROPERTIES: [&str; 6] = [
+    "Name",
+    "Parent",
+    "Source",
+    "Tags",
+    "Attributes",
+    "AttributesSerialize",
+];
+
+// Properties Studio assigns to tell instances apart, which a rebuilt place gets new values for
+const IDENTITY_PROPERTIES: [&str; 2] = ["UniqueId", "HistoryId"];
+
+// How Rojo names the members of each flag and enum type it spells out in JSON
+const FACES: [(Faces, &str); 6] = [
+    (Faces::RIGHT, "Right"),
+    (Faces::TOP, "Top"),
+    (Faces::BACK, "Back"),
+    (Faces::LEFT, "Left"),
+    (Faces::BOTTOM, "Bottom"),
+    (Faces::FRONT, "Front"),
+];
+
+const AXES: [(Axes, &str); 3] = [(Axes::X, "X"), (Axes::Y, "Y"), (Axes::Z, "Z")];
+
+const FONT_WEIGHTS: [(FontWeight, &str); 9] = [
+    (FontWeight::Thin, "Thin"),
+    (FontWeight::ExtraLight, "ExtraLight"),
+    (FontWeight::Light, "Light"),
+    (FontWeight::Regular, "Regular"),
+    (FontWeight::Medium, "Medium"),
+    (FontWeight::SemiBold, "SemiBold"),
+    (FontWeight::Bold, "Bold"),
+    (FontWeight::ExtraBold, "ExtraBold"),
+    (FontWeight::Heavy, "Heavy"),
+];
+
+const FONT_STYLES: [(FontStyle, &str); 2] =
+    [(FontStyle::Normal, "Normal"), (FontStyle::Italic, "Italic")];
+
+fn name_of<T: PartialEq>(names: &[(T, &'static str)], value: T) -> Option<&'static str> {
+    names
+        .iter()
+        .find(|(candidate, _)| *candidate == value)
+        .map(|(_, name)| *name)
+}
+
+fn value_of<T: Copy>(names: &[(T, &str)], name: &str) -> Option<T> {
+    names
+        .iter()
+        .find(|(_, candidate)| *candidate == name)
+        .map(|(value, _)| *value)
+}
+
+fn is_ignored(name: &str) -> bool {
+    IGNORED_PROPERTIES.contains(&name) || IDENTITY_PROPERTIES.contains(&name)
+}
+
+/// Why `encode_variant` couldn't write a value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EncodeError {
+    /// Rojo can't express values of this type in JSON.
+    UnsupportedType(VariantType),
+    /// The value holds NaN or an infinity, which JSON has no numbers for.
+    NonFinite,
+}
+
+impl fmt::Display for EncodeError {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EncodeError::UnsupportedType(ty) => write!(formatter, "{:?}", ty),
+            EncodeError::NonFinite => formatter.write_str("not a finite number"),
+        }
+    }
+}
+
+/// serde_json writes NaN and infinities as `null`, so a `null` anywhere in an encoded value
+/// means one of its numbers can't be written.
+fn contains_null(value: &Value) -> bool {
+    match value {
+        Value::Null => true,
+        Value::Array(values) => values.iter().any(contains_null),
+        Value::Object(values) => values.values().any(contains_null),
+        _ => false,
+    }
+}
+
+fn vector2(x: f32, y: f32) -> Value {
+    json!([x, y])
+}
+
+fn vector3(x: f32, y: f32, z: f32) -> Value {
+    json!([x, y, z])
+}
+
+fn cframe(value: &rbx_dom_weak::types::CFrame) -> Value {
+    let orientation = &value.orientation;
+    json!({
+        "position": vector3(value.position.x, value.position.y, value.position.z),
+        "orientation": [
+            vector3(orientation.x.x, orientation.x.y, orientation.x.z),
+            vector3(orientation.y.x, orientation.y.y, orientation.y.z),
+            vector3(orientation.z.x, orientation.z.y, orientation.z.z),
+        ],
+    })
+}
+
+/// Encodes a value in Rojo's explicit property syntax, e.g. `{ "Vector3": [1, 2, 3] }`.
+/// Fails for types Rojo can't express in JSON, and for numbers JSON can't hold.
+pub fn encode_variant(value: &Variant) -> Result<Value, EncodeError> {
+    let (type_name, encoded) = match value {
+        Variant::Bool(value) => ("Bool", json!(value)),
+        Variant::String(value) => ("String", json!(value)),
+        Variant::Int32(value) => ("Int32", json!(value)),
+        Variant::Int64(value) => ("Int64", json!(value)),
+        Variant::Float32(value) => ("Float32", json!(value)),
+        Variant::Float64(value) => ("Float64", json!(value)),
+        Variant::Enum(value) => ("Enum", json!(value.to_u32())),
+        Variant::BrickColor(value) => ("BrickColor", json!(*value as u16)),
+        Variant::ContentId(value) => ("ContentId", json!(value.as_str())),
+        Variant::Color3(value) => ("Color3", json!([value.r, value.g, value.b])),
+        Variant::Color3uint8(value) => ("Color3uint8", json!([value.r, value.g, value.b])),
+        Variant::Vector2(value) => ("Vector2", vector2(value.x, value.y)),
+        Variant::Vector2int16(value) => ("Vector2int16", json!([value.x, value.y])),
+        Variant::Vector3(value) => ("Vector3", vector3(value.x, value.y, value.z)),
+        Variant::Vector3int16(value) => ("Vector3int16", json!([value.x, value.y, value.z])),
+        Variant::UDim(value) => ("UDim", json!([value.scale, value.offset])),
+        Variant::UDim2(value) => (
+            "UDim2",
+            json!([
+                [value.x.scale, value.x.offset],
+                [value.y.scale, value.y.offset]
+            ]),
+        ),
+        Variant::Rect(value) => (
+            "Rect",
+            json!([
+                vector2(value.min.x, value.min.y),
+                vector2(value.max.x, value.max.y)
+            ]),
+        ),
+        Variant::NumberRange(value) => ("NumberRange", json!([value.min, value.max])),
+        Variant::NumberSequence(value) => (
+            "NumberSequence",
+            json!({
+                "keypoints": value
+                    .keypoints
+                    .iter()
+                    .map(|keypoint| json!({
+                        "time": keypoint.time,
+                        "value": keypoint.value,
+                        "envelope": keypoint.envelope,
+                    }))
+                    .collect::<Vec<_>>(),
+            }),
+        ),
+        Variant::ColorSequence(value) => (
+            "ColorSequence",
+            json!({
+                "keypoints": value
+                    .keypoints
+                    .iter()
+                    .map(|keypoint| json!({
+                        "time": keypoint.time,
+                        "color": [keypoint.color.r, keypoint.color.g, keypoint.color.b],
+                    }))
+                    .collect::<Vec<_>>(),
+            }),
+        ),
+        Variant::Tags(value) => ("Tags", json!(value.iter().collect::<Vec<_>>())),
+        Variant::CFrame(value) => ("CFrame", cframe(value)),
+        Variant::OptionalCFrame(value) => (
+            "OptionalCFrame",
+            match value {
+                Some(value) => cframe(value),
+                None => Value::Null,
+            },
+        ),
+        Variant::Font(value) => match (
+            name_of(&FONT_WEIGHTS, value.weight),
+            name_of(&FONT_STYLES, value.style),
+        ) {
+            (Some(weight), Some(style)) => (
+                "Font",
+                json!({ "family": value.family, "weight": weight, "style": style }),
+            ),
+            _ => return Err(EncodeError::UnsupportedType(VariantType::Font)),
+        },
+        Variant::PhysicalProperties(value) => (
+            "PhysicalProperties",
+            match value {
+                PhysicalProperties::Default => json!("Default"),
+                PhysicalProperties::Custom(value) => json!({
+                    "density": value.density,
+                    "friction": value.friction,
+                    "elasticity": value.elasticity,
+                    "frictionWeight": value.friction_weight,
+                    "elasticityWeight": value.elasticity_weight,
+                }),
+            },
+        ),
+        Variant::Faces(value) => (
+            "Faces",
+            json!(FACES
+                .iter()
+                .filter(|(face, _)| value.contains(*face))
+                .map(|(_, name)| *name)
+                .collect::<Vec<_>>()),
+        ),
+        Variant::Axes(value) => (
+            "Axes",
+            json!(AXES
+                .iter()
+                .filter(|(axis, _)| value.contains(*axis))
+                .map(|(_, name)| *name)
+                .collect::<Vec<_>>()),
+        ),
+        // Only content pointing at an asset has a JSON form, not content holding an object
+        Variant::Content(value) => match value.as_uri() {
+            Some(uri) => ("Content", json!(uri)),
+            None => return Err(EncodeError::UnsupportedType(VariantType::Content)),
+        },
+        _ => return Err(EncodeError::UnsupportedType(value.ty())),
+    };
+
+    // An unset OptionalCFrame is the only value that's meant to be written as `null`
+    if contains_null(&encoded) && !matches!(value, Variant::OptionalCFrame(None)) {
+        return Err(EncodeError::NonFinite);
+    }
+
+    let mut explicit = serde_json::Map::new();
+    explicit.insert(type_name.to_string(), encoded);
+    Ok(Value::Object(explicit))
+}
+
+fn floats<const N: usize>(value: &Value) -> Option<[f32; N]> {
+    let array = value.as_array()?;
+    if array.len() != N {
+        return None;
+    }
+
+    let mut floats = [0.0; N];
+    for (float, value) in floats.iter_mut().zip(array) {
+        *float = value.as_f64()? as f32;
+    }
+
+    Some(floats)
+}
+
+fn integers<const N: usize>(value: &Value) -> Option<[i64; N]> {
+    let array = value.as_array()?;
+    if array.len() != N {
+        return None;
+    }
+
+    let mut integers = [0; N];
+    for (integer, value) in integers.iter_mut().zip(array) {
+        *integer = value.as_i64()?;
+    }
+
+    Some(integers)
+}
+
+fn decode_vector2(value: &Value) -> Option<Vector2> {
+    let [x, y] = floats(value)?;
+    Some(Vector2::new(x, y))
+}
+
+fn decode_vector3(value: &Value) -> Option<Vector3> {
+    let [x, y, z] = floats(value)?;
+    Some(Vector3::new(x, y, z))
+}
+
+fn decode_udim(value: &Value) -> Option<UDim> {
+    let array = value.as_array()?;
+    match array.as_slice() {
+        [scale, offset] => Some(UDim::new(
+            scale.as_f64()? as f32,
+            i32::try_from(offset.as_i64()?).ok()?,
+        )),
+        _ => None,
+    }
+}
+
+fn decode_cframe(value: &Value) -> Option<CFrame> {
+    let position = decode_vector3(value.get("position")?)?;
+    let orientation = value.get("orientation")?.as_array()?;
+
+    match orientation.as_slice() {
+        [x, y, z] => Some(CFrame::new(
+            position,
+            Matrix3::new(decode_vector3(x)?, decode_vector3(y)?, decode_vector3(z)?),
+        )),
+        _ => None,
+    }
+}
+
+/// The inverse of `encode_variant`: reads a value written in Rojo's explicit property syntax.
+/// Returns `None` if it's malformed or of a type `encode_variant` never writes.
+pub fn decode_variant(value: &Value) -> Option<Variant> {
+    let object = value.as_object()?;
+    if object.len() != 1 {
+        return None;
+    }
+
+    let (type_name, value) = object.iter().next()?;
+
+    Some(match type_name.as_str() {
+        "Bool" => Variant::Bool(value.as_bool()?),
+        "String" => Variant::String(value.as_str()?.to_string()),
+        "Int32" => Variant::Int32(i32::try_from(value.as_i64()?).ok()?),
+        "Int64" => Variant::Int64(value.as_i64()?),
+        "Float32" => Variant::Float32(value.as_f64()? as f32),
+        "Float64" => Variant::Float64(value.as_f64()?),
+        "Enum" => Variant::Enum(Enum::from_u32(u32::try_from(value.as_u64()?).ok()?)),
+        "BrickColor" => Variant::BrickColor(BrickColor::from_number(
+            u16::try_from(value.as_u64()?).ok()?,
+        )?),
+        "ContentId" => Variant::ContentId(ContentId::from(value.as_str()?)),
+        "Color3" => {
+            let [r, g, b] = floats(value)?;
+            Variant::Color3(Color3::new(r, g, b))
+        }
+        "Color3uint8" => {
+            let [r, g, b] = integers(value)?;
+            Variant::Color3uint8(Color3uint8::new(
+                u8::try_from(r).ok()?,
+                u8::try_from(g).ok()?,
+                u8::try_from(b).ok()?,
+            ))
+        }
+        "Vector2" => Variant::Vector2(decode_vector2(value)?),
+        "Vector2int16" => {
+            let [x, y] = integers(value)?;
+            Variant::Vector2int16(Vector2int16::new(
+                i16::try_from(x).ok()?,
+                i16::try_from(y).ok()?,
+            ))
+        }
+        "Vector3" => Variant::Vector3(decode_vector3(value)?),
+        "Vector3int16" => {
+            let [x, y, z] = integers(value)?;
+            Variant::Vector3int16(Vector3int16::new(
+                i16::try_from(x).ok()?,
+                i16::try_from(y).ok()?,
+                i16::try_from(z).ok()?,
+            ))
+        }
+        "UDim" => Variant::UDim(decode_udim(value)?),
+        "UDim2" => match value.as_array()?.as_slice() {
+            [x, y] => Variant::UDim2(UDim2::new(decode_udim(x)?, decode_udim(y)?)),
+            _ => return None,
+        },
+        "Rect" => match value.as_array()?.as_slice() {
+            [min, max] => Variant::Rect(Rect::new(decode_vector2(min)?, decode_vector2(max)?)),
+            _ => return None,
+        },
+        "NumberRange" => {
+            let [min, max] = floats(value)?;
+            Variant::NumberRange(NumberRange::new(min, max))
+        }
+        "NumberSequence" => {
+            let mut keypoints = Vec::new();
+            for keypoint in value.get("keypoints")?.as_array()? {
+                keypoints.push(NumberSequenceKeypoint::new(
+                    keypoint.get("time")?.as_f64()? as f32,
+                    keypoint.get("value")?.as_f64()? as f32,
+                    keypoint.get("envelope")?.as_f64()? as f32,
+                ));
+            }
+
+            Variant::NumberSequence(NumberSequence { keypoints })
+        }
+        "ColorSequence" => {
+            let mut keypoints = Vec::new();
+            for keypoint in value.get("keypoints")?.as_array()? {
+                let [r, g, b] = floats(keypoint.get("color")?)?;
+                keypoints.push(ColorSequenceKeypoint::new(
+                    keypoint.get("time")?.as_f64()? as f32,
+                    Color3::new(r, g, b),
+                ));
+            }
+
+            Variant::ColorSequence(ColorSequence { keypoints })
+        }
+        "Tags" => {
+            let mut tags = Vec::new();
+            for tag in value.as_array()? {
+                tags.push(tag.as_str()?.to_string());
+            }
+
+            Variant::Tags(Tags::from(tags))
+        }
+        "CFrame" => Variant::CFrame(decode_cframe(value)?),
+        "OptionalCFrame" => match value {
+            Value::Null => Variant::OptionalCFrame(None),
+            value => Variant::OptionalCFrame(Some(decode_cframe(value)?)),
+        },
+        "Font" => Variant::Font(Font::new(
+            value.get("family")?.as_str()?,
+            value_of(&FONT_WEIGHTS, value.get("weight")?.as_str()?)?,
+            value_of(&FONT_STYLES, value.get("style")?.as_str()?)?,
+        )),
+        "PhysicalProperties" => match value {
+            Value::String(default) if default == "Default" => {
+                Variant::PhysicalProperties(PhysicalProperties::Default)
+            }
+            value => {
+                Variant::PhysicalProperties(PhysicalProperties::Custom(CustomPhysicalProperties {
+                    density: value.get("density")?.as_f64()? as f32,
+                    friction: value.get("friction")?.as_f64()? as f32,
+                    elasticity: value.get("elasticity")?.as_f64()? as f32,
+                    friction_weight: value.get("frictionWeight")?.as_f64()? as f32,
+                    elasticity_weight: value.get("elasticityWeight")?.as_f64()? as f32,
+                }))
+            }
+        },
+        "Faces" => {
+            let mut faces = Faces::empty();
+            for face in value.as_array()? {
+                faces |= value_of(&FACES, face.as_str()?)?;
+            }
+
+            Variant::Faces(faces)
+        }
+        "Axes" => {
+            let mut axes = Axes::empty();
+            for axis in value.as_array()? {
+                axes |= value_of(&AXES, axis.as_str()?)?;
+            }
+
+            Variant::Axes(axes)
+        }
+        "Content" => Variant::Content(Content::from_uri(value.as_str()?)),
+        _ => return None,
+    })
+}
+
+/// Decodes the `attributes` of a meta file or project node back into an `Attributes`.
+/// Values that can't be decoded are left out with a warning.
+pub fn decode_attributes(attributes: &Properties) -> Attributes {
+    let mut decoded = Attributes::new();
+
+    for (name, value) in attributes {
+        match decode_variant(value) {
+            Some(value) => {
+                decoded.insert(name.clone(), value);
+            }
+
+            None => warn!("couldn't decode attribute {} from {}", name, value),
+        }
+    }
+
+    decoded
+}
+
+fn find_property<'db>(
+    db: &'db rbx_reflection::ReflectionDatabase<'db>,
+    class: &'db ClassDescriptor<'db>,
+    name: &str,
+) -> Option<&'db PropertyDescriptor<'db>> {
+    let mut current = Some(class);
+
+    while let Some(descriptor) = current {
+        if let Some(property) = descriptor.properties.get(name) {
+            return Some(property);
+        }
+
+        current = descriptor
+            .superclass
+            .as_ref()
+            .and_then(|superclass| db.classes.get(superclass.as_ref()));
+    }
+
+    None
+}
+
+/// Finds the canonical property behind `name`, which is the name Rojo wants and the one
+/// defaults are listed under. Places decoded without reflection keep the names properties are
+/// serialized with, which can be different, like `size` for `Size`.
+fn canonical_property<'db>(
+    db: &'db rbx_reflection::ReflectionDatabase<'db>,
+    class: &'db ClassDescriptor<'db>,
+    name: &str,
+) -> Option<&'db PropertyDescriptor<'db>> {
+    if let Some(property) = find_property(db, class, name) {
+        return match &property.kind {
+            PropertyKind::Alias { alias_for, .. } => find_property(db, class, alias_for),
+            _ => Some(property),
+        };
+    }
+
+    let mut current = Some(class);
+
+    while let Some(descriptor) = current {
+        let serialized_as = descriptor.properties.values().find(|property| {
+            matches!(
+                &property.kind,
+                PropertyKind::Canonical {
+                    serialization: PropertySerialization::SerializesAs(serialized),
+                    ..
+                } if serialized == name
+            )
+        });
+
+        if serialized_as.is_some() {
+            return serialized_as;
+        }
+
+        current = descriptor
+            .superclass
+            .as_ref()
+            .and_then(|superclass| db.classes.get(superclass.as_ref()));
+    }
+
+    None
+}
+
+/// Converts a value to the type its canonical property has, where they differ. Places decoded
+/// without reflection can have a `Color3uint8` where the property is a `Color3`, for example.
+fn canonical_value<'a>(
+    class: &ClassDescriptor,
+    name: &str,
+    value: &'a Variant,
+) -> Cow<'a, Variant> {
+    match (value, class.default_properties.get(name)) {
+        (Variant::Color3uint8(color), Some(Variant::Color3(_))) => {
+            Cow::Owned(Variant::Color3(Color3::new(
+                f32::from(color.r) / 255.0,
+                f32::from(color.g) / 255.0,
+                f32::from(color.b) / 255.0,
+            )))
+        }
+        _ => Cow::Borrowed(value),
+    }
+}
+
+fn is_default(class: &ClassDescriptor, name: &str, value: &Variant) -> bool {
+    match value {
+        Variant::Ref(referent) => referent.is_none(),
+        _ => class.default_properties.get(name) == Some(value),
+    }
+}
+
+fn is_writable(property: &PropertyDescriptor) -> bool {
+    matches!(property.kind, PropertyKind::Canonical { .. })
+        && matches!(
+            property.scriptability,
+            Scriptability::ReadWrite | Scriptability::Write | Scriptability::Custom
+        )
+}
+
+/// Whether a property is saved in place files, so it carries over through model files even
+/// when Rojo can't write it.
+fn is_serialized(property: &PropertyDescriptor) -> bool {
+    matches!(
+        property.kind,
+        PropertyKind::Canonical {
+            serialization: PropertySerialization::Serializes
+                | PropertySerialization::SerializesAs(_),
+            ..
+        }
+    )
+}
+
+/// Collects the properties of an instance that differ from their defaults, encoded for Rojo.
+/// Properties Rojo can't write (read-only, internal, of an unsupported type, or not finite)
+/// are left out with a warning.
+pub fn instance_properties(instance: &Instance) -> Properties {
+    let (properties, unrepresentable) = encode_properties(instance);
+
+    for name in unrepresentable {
+        warn!(
+            "can't represent {}.{} in JSON, leaving it out",
+            instance.name, name
+        );
+    }
+
+    properties
+}
+
+/// Like `instance_properties`, but also returns the names of non-default properties that
+/// couldn't be encoded, so callers can fall back to a model file instead of dropping them.
+pub fn encode_properties(instance: &Instance) -> (Properties, Vec<String>) {
+    let mut properties = Properties::new();
+    let mut unrepresentable = Vec::new();
+
+    insert_reflected_properties(instance, &mut properties, &mut unrepresentable);
+
+    let tags = instance_tags(instance);
+    if !tags.is_empty() {
+        properties.insert("Tags".to_string(), json!({ "Tags": tags }));
+    }
+
+    (properties, unrepresentable)
+}
+
+fn insert_reflected_properties(
+    instance: &Instance,
+    properties: &mut Properties,
+    unrepresentable: &mut Vec<String>,
+) {
+    let relevant_properties = instance
+        .properties
+        .iter()
+        .filter(|(name, _)| !is_ignored(name.as_str()));
+
+    let db = match rbx_reflection_database::get() {
+        Ok(db) => db,
+        Err(error) => {
+            debug!("couldn't get reflection database: {:?}", error);
+            unrepresentable.extend(relevant_properties.map(|(name, _)| name.to_string()));
+            return;
+        }
+    };
+
+    let class = match db.classes.get(instance.class.as_str()) {
+        Some(class) => class,
+        None => {
+            unrepresentable.extend(relevant_properties.map(|(name, _)| name.to_string()));
+            return;
+        }
+    };
+
+    for (name, value) in relevant_properties {
+        let property = match canonical_property(db, class, name.as_str()) {
+            Some(property) => property,
+            None => {
+                unrepresentable.push(name.to_string());
+                continue;
+            }
+        };
+
+        let name = property.name.as_ref();
+        let value = canonical_value(class, name, value);
+        if is_default(class, name, &value) {
+            continue;
+        }
+
+        // Rojo can't set these, but they're saved with the instance, so only a model file
+        // keeps them
+        if !is_writable(property) {
+            if is_serialized(property) {
+                unrepresentable.push(format!("{} (not writable)", name));
+            }
+            continue;
+        }
+
+        match encode_variant(&value) {
+            Ok(encoded) => {
+                properties.insert(name.to_string(), encoded);
+            }
+
+            Err(error) => unrepresentable.push(format!("{} ({})", name, error)),
+        }
+    }
+}
+
+/// Reads the CollectionService tags of an instance.
+pub fn instance_tags(instance: &Instance) -> Vec<String> {
+    match instance.properties.get(&ustr::ustr("Tags")) {
+        Some(Variant::Tags(tags)) => tags.iter().map(str::to_string).collect(),
+
+        // Without reflection, tags stay as the raw null-separated string
+        Some(Variant::BinaryString(bytes)) => String::from_utf8_lossy(bytes.as_ref())
+            .split('\0')
+            .filter(|tag| !tag.is_empty())
+            .map(str::to_string)
+            .collect(),
+
+        _ => Vec::new(),
+    }
+}
+
+/// Encodes an instance's attributes for the `attributes` field of meta files.
+/// Attributes Rojo can't express, by their type or a value JSON can't hold, are skipped with a
+/// warning.
+pub fn instance_attributes(instance: &Instance) -> Properties {
+    let (attributes, unrepresentable) = encode_attributes(instance);
+
+    for name in unrepresentable {
+        warn!(
+            "attribute {} on {} can't be represented in JSON, skipping it",
+            name, instance.name
+        );
+    }
+
+    attributes
+}
+
+/// Like `instance_attributes`, but returns the attributes it couldn't encode instead of
+/// warning about them.
+pub fn encode_attributes(instance: &Instance) -> (Properties, Vec<String>) {
+    let mut encoded_attributes = Properties::new();
+    let mut unrepresentable = Vec::new();
+
+    let decoded;
+    let attributes = match instance.properties.get(&ustr::ustr("Attributes")) {
+        Some(Variant::Attributes(attributes)) => attributes,
+
+        // Files decoded without reflection keep attributes in their serialized form
+        _ => match instance.properties.get(&ustr::ustr("AttributesSerialize")) {
+            Some(Variant::BinaryString(bytes)) if !bytes.as_ref().is_empty() => {
+                match Attributes::from_reader(bytes.as_ref()) {
+                    Ok(attributes) => {
+                        decoded = attributes;
+                        &decoded
+                    }
+
+                    Err(error) => {
+                        warn!(
+                            "couldn't decode attributes of {}: {:?}",
+                            instance.name, error
+                        );
+                        unrepresentable.push("AttributesSerialize".to_string());
+                        return (encoded_attributes, unrepresentable);
+                    }
+                }
+            }
+
+            _ => return (encoded_attributes, unrepresentable),
+        },
+    };
+
+    for (name, value) in attributes.iter() {
+        match encode_variant(value) {
+            Ok(encoded) => {
+                encoded_attributes.insert(name.to_string(), encoded);
+            }
+
+            Err(error) => unrepresentable.push(format!("{} ({})", name, error)),
+        }
+    }
+
+    (encoded_attributes, unrepresentable)
+}
+
+/// Everything an export should carry over from an instance, for comparing two versions of it:
+/// the writable or serialized properties that differ from their defaults, along with tags and
+/// attributes.
+/// Properties go by their canonical names, so trees decoded with and without reflection
+/// compare the same. Values JSON can't hold are kept by their debug representation, and
+/// references only by whether they're set, since referents differ between trees.
+pub fn comparable_properties(instance: &Instance) -> Properties {
+    let mut properties = Properties::new();
+
+    let db = rbx_reflection_database::get().ok();
+    let class = db.and_then(|db| db.classes.get(instance.class.as_str()));
+
+    for (name, raw_value) in instance.properties.iter() {
+        let mut name = name.as_str();
+        if is_ignored(name) {
+            continue;
+        }
+
+        let mut value = Cow::Borrowed(raw_value);
+        if let (Some(db), Some(class)) = (db, class) {
+            if let Some(property) = canonical_property(db, class, name) {
+                if !is_writable(property) && !is_serialized(property) {
+                    continue;
+                }
+
+                name = property.name.as_ref();
+                value = canonical_value(class, name, raw_value);
+            }
+
+            if is_default(class, name, &value) {
+                continue;
+            }
+        }
+
+        let compared = match value.as_ref() {
+            Variant::Ref(referent) if referent.is_none() => continue,
+            Variant::Ref(_) => json!({ "Ref": "set" }),
+            value => encode_variant(value).unwrap_or_else(|_| json!(format!("{:?}", value))),
+        };
+
+        properties.insert(name.to_string(), compared);
+    }
+
+    let tags = instance_tags(instance);
+    if !tags.is_empty() {
+        properties.insert("Tags".to_string(), json!({ "Tags": tags }));
+    }
+
+    let (attributes, _) = encode_attributes(instance);
+    if !attributes.is_empty() {
+        properties.insert(
+            "Attributes".to_string(),
+            Value::Object(attributes.into_iter().collect()),
+        );
+    }
+
+    properties
+}
@@ -0,0 +1,93 @@
+use rbx_dom_weak::types::Ref;
+use serde::Serialize;
+use std::{fmt, path::PathBuf};
+
+/// Why an instance didn't make it into the project.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SkipReason {
+    /// Only scripts are being exported, and there are none in this instance.
+    NoScripts,
+    /// The service isn't one Rojo projects usually sync.
+    IgnoredService,
+    /// The instance had to be written as a model, but serializing it failed.
+    SerializationFailed,
+    /// An ancestor was written without its descendants.
+    AncestorNotTraversed,
+    /// Only chosen subtrees are being exported, and this isn't in any of them.
+    NotSelected,
+    /// The include and exclude filters leave it out.
+    Filtered,
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str(match self {
+            SkipReason::NoScripts => "contains no scripts",
+            SkipReason::IgnoredService => "service isn't exported",
+            SkipReason::SerializationFailed => "couldn't be serialized as a model",
+            SkipReason::AncestorNotTraversed => "an ancestor was exported without its descendants",
+            SkipReason::NotSelected => "isn't in a subtree chosen for export",
+            SkipReason::Filtered => "left out by the filters",
+        })
+    }
+}
+
+/// What happened to an instance.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Decision {
+    /// Written as its own script, folder, or file.
+    Exported,
+    /// Included in a model file written for it or one of its ancestors.
+    Packed,
+    /// Left out of the project.
+    Skipped,
+    /// Written under a different file name than its own. Comes alongside the entry for how
+    /// the instance was written.
+    Renamed,
+}
+
+/// One decision about one instance.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InstanceReport {
+    /// The DataModel path of the instance, e.g. `Workspace.Map`.
+    pub instance: String,
+    /// The instance in the tree the report is about. Unlike the path, this tells apart
+    /// siblings that share a name.
+    #[serde(skip)]
+    pub referent: Ref,
+    pub class_name: String,
+    pub decision: Decision,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+
+    /// Where the instance was written, relative to the project's `src` folder.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
+}
+
+/// Two paths that would be the same file on a case-insensitive filesystem.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Collision {
+    pub path: PathBuf,
+    pub existing: PathBuf,
+}
+
+/// What `process_instructions` decided along the way, beyond the instructions themselves.
+/// Serializes to JSON so reports of different runs can be diffed.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Report {
+    pub instances: Vec<InstanceReport>,
+    pub collisions: Vec<Collision>,
+}
+
+impl Report {
+    /// The instances with the given decision, in the order they were visited.
+    pub fn with_decision(&self, decision: Decision) -> impl Iterator<Item = &InstanceReport> {
+        self.instances
+            .iter()
+            .filter(move |entry| entry.decision == decision)
+    }
+}
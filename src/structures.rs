@@ -1,3 +1,7 @@
+use crate::{
+    error::Error,
+    properties::{instance_attributes, instance_properties, Properties},
+};
 use rbx_dom_weak::Instance;
 use serde::{Deserialize, Serialize, Serializer};
 use std::{
@@ -37,24 +41,130 @@ pub struct TreePartition {
     #[serde(skip_serializing_if = "Option::is_none")]
     #[serde(serialize_with = "replace_backslashes")]
     pub path: Option<PathBuf>,
-    // #[serde(rename = "$properties")]
-    // #[serde(skip_serializing_if = "BTreeMap::is_empty")]
-    // pub properties: BTreeMap<String, RbxValue>,
+
+    #[serde(rename = "$properties")]
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub properties: Properties,
+
+    #[serde(rename = "$attributes")]
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub attributes: Properties,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
 pub(crate) struct MetaFile {
+    #[serde(rename = "name")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+
     #[serde(rename = "className")]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub class_name: Option<String>,
 
-    // #[serde(rename = "properties")]
-    // #[serde(skip_serializing_if = "BTreeMap::is_empty")]
-    // pub properties: BTreeMap<String, RbxValue>,
+    #[serde(rename = "properties")]
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub properties: Properties,
+
+    #[serde(rename = "attributes")]
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub attributes: Properties,
+
     #[serde(rename = "ignoreUnknownInstances")]
     pub ignore_unknown_instances: bool,
 }
 
+impl Default for MetaFile {
+    fn default() -> Self {
+        MetaFile {
+            name: None,
+            class_name: None,
+            properties: Properties::new(),
+            attributes: Properties::new(),
+            ignore_unknown_instances: true,
+        }
+    }
+}
+
+impl MetaFile {
+    pub fn from_instance(instance: &Instance) -> Self {
+        MetaFile {
+            properties: instance_properties(instance),
+            attributes: instance_attributes(instance),
+            ..MetaFile::default()
+        }
+    }
+
+    /// Whether the meta file carries anything Rojo couldn't infer without it.
+    pub fn is_empty(&self) -> bool {
+        self.name.is_none()
+            && self.class_name.is_none()
+            && self.properties.is_empty()
+            && self.attributes.is_empty()
+    }
+}
+
+/// A Rojo JSON model, written as `.model.json`.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub(crate) struct ModelJson {
+    #[serde(rename = "name")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+
+    #[serde(rename = "className")]
+    pub class_name: String,
+
+    #[serde(rename = "properties")]
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub properties: Properties,
+
+    #[serde(rename = "attributes")]
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub attributes: Properties,
+
+    #[serde(rename = "children")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<ModelJson>,
+}
+
+/// One entry of a LocalizationTable's `Contents`, as Roblox stores it.
+#[derive(Clone, Debug, Deserialize)]
+pub(crate) struct LocalizationEntry {
+    #[serde(default)]
+    pub key: Option<String>,
+
+    #[serde(default)]
+    pub source: Option<String>,
+
+    #[serde(default)]
+    pub context: Option<String>,
+
+    #[serde(default, alias = "examples")]
+    pub example: Option<String>,
+
+    #[serde(default)]
+    pub values: BTreeMap<String, String>,
+}
+
+/// Settings for the generated `default.project.json`.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProjectOptions {
+    /// The name of the project, "project" if there isn't one. Model projects are always
+    /// named after their root, since that's the name Rojo gives the model.
+    pub name: Option<String>,
+    /// The port `rojo serve` listens on.
+    pub serve_port: Option<u16>,
+    /// The only places `rojo serve` will sync into.
+    pub serve_place_ids: Vec<u64>,
+    /// The place ID Rojo gives the place it builds.
+    pub place_id: Option<u64>,
+    /// The game ID Rojo gives the place it builds.
+    pub game_id: Option<u64>,
+    /// Globs of files in the project that Rojo ignores.
+    pub glob_ignore_paths: Vec<String>,
+}
+
 #[derive(Clone, Debug)]
 pub enum Instruction<'a> {
     AddToTree {
@@ -62,6 +172,18 @@ pub enum Instruction<'a> {
         partition: TreePartition,
     },
 
+    /// Makes the project a model project whose tree is this partition, named `name`, rather
+    /// than a place with services under a DataModel.
+    SetTreeRoot {
+        name: String,
+        partition: TreePartition,
+    },
+
+    /// Applies settings to the project file. Comes before any other instruction.
+    ConfigureProject {
+        options: ProjectOptions,
+    },
+
     CreateFile {
         filename: Cow<'a, Path>,
         contents: Cow<'a, [u8]>,
@@ -80,23 +202,38 @@ impl<'a> Instruction<'a> {
         }
     }
 
+    /// A node of the tree with the instance's class and properties, but no files behind it.
+    pub fn node(instance: &Instance) -> TreePartition {
+        TreePartition {
+            path: None,
+            ..Instruction::partition(instance, PathBuf::new())
+        }
+    }
+
     pub fn partition(instance: &Instance, path: PathBuf) -> TreePartition {
         TreePartition {
             class_name: instance.class.to_string(),
             children: BTreeMap::new(),
             ignore_unknown_instances: true,
             path: Some(path),
+            properties: instance_properties(instance),
+            attributes: instance_attributes(instance),
         }
     }
 }
 
 pub trait InstructionReader {
-    fn finish_instructions(&mut self) {}
-    fn read_instruction<'a>(&mut self, instruction: Instruction<'a>);
+    fn finish_instructions(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
 
-    fn read_instructions<'a>(&mut self, instructions: Vec<Instruction<'a>>) {
+    fn read_instruction<'a>(&mut self, instruction: Instruction<'a>) -> Result<(), Error>;
+
+    fn read_instructions<'a>(&mut self, instructions: Vec<Instruction<'a>>) -> Result<(), Error> {
         for instruction in instructions {
-            self.read_instruction(instruction);
+            self.read_instruction(instruction)?;
         }
+
+        Ok(())
     }
 }
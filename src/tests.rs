@@ -1,12 +1,32 @@
-use crate::{filesystem::FileSystem, process_instructions, structures::*, ExportMode};
+use crate::{
+    check::{check_project, ChangeKind},
+    config::{Config, CONFIG_FILE},
+    dry_run::{DryRun, PlannedEntry},
+    error::{Cause, Error, Operation},
+    filesystem::FileSystem,
+    filter::Filters,
+    process_instructions,
+    properties::{decode_variant, encode_variant, EncodeError},
+    report::{Decision, InstanceReport, Report},
+    structures::*,
+    verify::{build_project_with_rojo, find_rojo, verify_project, verify_rebuilt, MismatchKind},
+    ExportMode, ExportOptions, ModelFormat, ScriptExtension,
+};
 use log::info;
 use pretty_assertions::assert_eq;
-use rbx_dom_weak::types::Variant;
+use rbx_dom_weak::{
+    types::{
+        Axes, Content, CustomPhysicalProperties, Faces, Font, FontStyle, FontWeight,
+        PhysicalProperties, Variant, Vector3,
+    },
+    InstanceBuilder, WeakDom,
+};
 use serde::{Deserialize, Serialize};
 use std::{
     collections::{BTreeMap, HashMap},
     fs,
     io::ErrorKind,
+    path::{Path, PathBuf},
     time::Instant,
 };
 
@@ -27,6 +47,8 @@ struct VirtualFileSystem {
     files: BTreeMap<String, VirtualFile>,
     tree: BTreeMap<String, TreePartition>,
     #[serde(skip)]
+    root: Option<(String, TreePartition)>,
+    #[serde(skip)]
     finished: bool,
 }
 
@@ -52,7 +74,11 @@ fn assert_vfs_contains(actual: &VirtualFileSystem, expected: &VirtualFileSystem,
             .get(name)
             .unwrap_or_else(|| panic!("missing tree entry {} in {}", name, context));
 
-        assert_eq!(actual_partition, expected_partition, "tree mismatch at {}", context);
+        assert_eq!(
+            actual_partition, expected_partition,
+            "tree mismatch at {}",
+            context
+        );
     }
 }
 
@@ -81,16 +107,23 @@ impl PartialEq<VirtualFileSystem> for VirtualFileSystem {
 }
 
 impl InstructionReader for VirtualFileSystem {
-    fn finish_instructions(&mut self) {
+    fn finish_instructions(&mut self) -> Result<(), Error> {
         self.finished = true;
+        Ok(())
     }
 
-    fn read_instruction<'a>(&mut self, instruction: Instruction<'a>) {
+    fn read_instruction<'a>(&mut self, instruction: Instruction<'a>) -> Result<(), Error> {
         match instruction {
             Instruction::AddToTree { name, partition } => {
                 self.tree.insert(name, partition);
             }
 
+            Instruction::SetTreeRoot { name, partition } => {
+                self.root = Some((name, partition));
+            }
+
+            Instruction::ConfigureProject { .. } => {}
+
             Instruction::CreateFile { filename, contents } => {
                 let parent = filename
                     .parent()
@@ -127,18 +160,37 @@ impl InstructionReader for VirtualFileSystem {
                 };
 
                 let contents_string = String::from_utf8_lossy(&contents).into_owned();
-                let rbxmx = filename.ends_with(".rbxmx");
+                let model = if filename.ends_with(".rbxmx") {
+                    Some(
+                        rbx_xml::from_str_default(&contents_string)
+                            .expect("couldn't decode encoded xml"),
+                    )
+                } else if filename.ends_with(".rbxm") {
+                    Some(
+                        rbx_binary::from_reader(&*contents)
+                            .expect("couldn't decode encoded binary"),
+                    )
+                } else {
+                    None
+                };
+
                 system.files.insert(
                     filename,
                     VirtualFile {
-                        contents: if rbxmx {
-                            let tree = rbx_xml::from_str_default(&contents_string)
-                                .expect("couldn't decode encoded xml");
-                            let child_id = tree.root().children()[0];
-                            let child_instance = tree.get_by_ref(child_id).unwrap().clone();
-                            VirtualFileContents::Instance(child_instance.properties.to_owned())
-                        } else {
-                            VirtualFileContents::Bytes(contents_string)
+                        contents: match model {
+                            Some(tree) => {
+                                let child_id = tree.root().children()[0];
+                                let child_instance = tree.get_by_ref(child_id).unwrap();
+                                VirtualFileContents::Instance(
+                                    child_instance
+                                        .properties
+                                        .iter()
+                                        .map(|(key, value)| (key.to_string(), value.clone()))
+                                        .collect(),
+                                )
+                            }
+
+                            None => VirtualFileContents::Bytes(contents_string),
                         },
                     },
                 );
@@ -154,9 +206,141 @@ impl InstructionReader for VirtualFileSystem {
                 );
             }
         }
+
+        Ok(())
+    }
+}
+
+/// Every file path in the system, with model extensions normalized so that XML and binary
+/// output can be compared.
+fn model_agnostic_paths(vfs: &VirtualFileSystem, prefix: &str, paths: &mut Vec<String>) {
+    for (name, file) in &vfs.files {
+        let name = name.trim_end_matches(".rbxmx").trim_end_matches(".rbxm");
+        let path = format!("{}/{}", prefix, name);
+
+        if let VirtualFileContents::Vfs(system) = &file.contents {
+            model_agnostic_paths(system, &path, paths);
+        }
+
+        paths.push(path);
     }
 }
 
+fn check_expected_output(
+    path: &Path,
+    tree: &rbx_dom_weak::WeakDom,
+    options: &ExportOptions,
+    expected_name: &str,
+) -> VirtualFileSystem {
+    let mut vfs = VirtualFileSystem::default();
+    let time = Instant::now();
+    process_instructions(tree, &mut vfs, options).expect("couldn't process instructions");
+    info!(
+        "processing instructions for {:?} took {}ms",
+        path,
+        Instant::now().duration_since(time).as_millis()
+    );
+
+    let expected_path = path.join(expected_name);
+    assert!(vfs.finished, "finish_instructions was not called");
+
+    if let Ok(expected) = fs::read_to_string(&expected_path) {
+        let expected: VirtualFileSystem = serde_json::from_str(&expected).unwrap();
+        assert_vfs_contains(&vfs, &expected, "");
+    } else {
+        let output = serde_json::to_string_pretty(&vfs).unwrap();
+        fs::write(&expected_path, output).expect("couldn't write expected output");
+    }
+
+    vfs
+}
+
+/// Fixtures whose projects have to rebuild into exactly their source, under every set of
+/// options they pin down an output for.
+const ROUND_TRIP_FIXTURES: [&str; 4] = [
+    "containers",
+    "interleaved-models-and-scripts",
+    "screen-gui-with-text",
+    "script-free-models",
+];
+
+/// Writes the project for `tree` to a temporary folder, and checks that Rojo would build the
+/// same tree back from it, with nothing missing, doubled up or changed. With `rojo`, the
+/// project is also built by Rojo itself.
+fn assert_round_trips(tree: &WeakDom, options: &ExportOptions, name: &str, rojo: Option<&Path>) {
+    let root = std::env::temp_dir().join(format!("rbxlx-to-rojo-round-trip-{}", name));
+    fs::remove_dir_all(&root).ok();
+    fs::create_dir_all(&root).unwrap();
+
+    process_instructions(tree, &mut FileSystem::from_root(root.clone()), options)
+        .expect("couldn't write the project");
+
+    let verification = verify_project(tree, &root, options).expect("couldn't verify");
+    assert!(
+        verification.is_empty(),
+        "unexpected mismatches in {}: {}",
+        name,
+        verification
+    );
+
+    if let Some(rojo) = rojo {
+        let rebuilt = build_project_with_rojo(&root, rojo).expect("couldn't build with Rojo");
+        let verification = verify_rebuilt(tree, &rebuilt, options).expect("couldn't verify");
+        assert!(
+            verification.is_empty(),
+            "unexpected mismatches in {} built by Rojo: {}",
+            name,
+            verification
+        );
+    }
+
+    fs::remove_dir_all(&root).ok();
+}
+
+/// Round trips a fixture under every set of options it pins down an output for.
+fn assert_fixture_round_trips(
+    path: &Path,
+    tree: &WeakDom,
+    variants: &[(&str, ExportOptions)],
+    rojo: Option<&Path>,
+) {
+    let fixture = path.file_name().unwrap().to_string_lossy();
+    for (expected_name, options) in variants {
+        if path.join(expected_name).exists() {
+            let name = format!("{}-{}", fixture, expected_name.trim_end_matches(".json"));
+            assert_round_trips(tree, options, &name, rojo);
+        }
+    }
+}
+
+/// The outputs a fixture can pin down, as `output*.json` files, and the options for each.
+fn output_variants() -> [(&'static str, ExportOptions); 4] {
+    [
+        ("output.json", ExportOptions::default()),
+        (
+            "output.lua.json",
+            ExportOptions {
+                script_extension: ScriptExtension::Lua,
+                ..ExportOptions::default()
+            },
+        ),
+        (
+            "output.model-json.json",
+            ExportOptions {
+                model_json_threshold: Some(16),
+                ..ExportOptions::default()
+            },
+        ),
+        (
+            "output.containers-as-folders.json",
+            ExportOptions {
+                containers_as_folders: true,
+                ..ExportOptions::default()
+            },
+        ),
+    ]
+}
+
 #[test]
 fn run_tests() {
     let _ = env_logger::init();
@@ -177,27 +361,57 @@ fn run_tests() {
             Instant::now().duration_since(time).as_millis()
         );
 
-        let mut vfs = VirtualFileSystem::default();
-        let time = Instant::now();
-        process_instructions(&tree, &mut vfs, ExportMode::Full);
-        info!(
-            "processing instructions for {:?} took {}ms",
-            path,
-            Instant::now().duration_since(time).as_millis()
-        );
+        let vfs = check_expected_output(&path, &tree, &ExportOptions::default(), "output.json");
 
-        let mut expected_path = path.clone();
-        expected_path.push("output.json");
-        assert!(vfs.finished, "finish_instructions was not called");
+        // Fixtures can additionally pin down the output under other options
+        let variants = output_variants();
 
-        if let Ok(expected) = fs::read_to_string(&expected_path) {
-            let expected: VirtualFileSystem = serde_json::from_str(&expected).unwrap();
-            assert_vfs_contains(&vfs, &expected, "");
-        } else {
-            let output = serde_json::to_string_pretty(&vfs).unwrap();
-            fs::write(&expected_path, output).expect("couldn't write to output.json");
+        // The default output is always checked, above
+        for (expected_name, options) in &variants[1..] {
+            if path.join(expected_name).exists() {
+                check_expected_output(&path, &tree, options, expected_name);
+            }
+        }
+
+        let fixture = path.file_name().unwrap().to_string_lossy();
+        if ROUND_TRIP_FIXTURES.contains(&fixture.as_ref()) {
+            assert_fixture_round_trips(&path, &tree, &variants, None);
+        }
+
+        // The command line decodes places without reflection, which keeps properties under
+        // the names and types they're serialized with, like `size` and `Color3uint8`.
+        // Fixtures can pin down any of the outputs for that as `*.no-reflection.json`.
+        let decode_options = rbx_xml::DecodeOptions::new()
+            .property_behavior(rbx_xml::DecodePropertyBehavior::NoReflection);
+        let raw_tree = rbx_xml::from_str(&source, decode_options)
+            .expect("couldn't deserialize source.rbxmx without reflection");
+
+        for (expected_name, options) in &variants {
+            let expected_name = expected_name.replace(".json", ".no-reflection.json");
+            if path.join(&expected_name).exists() {
+                check_expected_output(&path, &raw_tree, options, &expected_name);
+            }
         }
 
+        // Binary models must decode and land in exactly the same places as XML ones
+        let mut binary_vfs = VirtualFileSystem::default();
+        let options = ExportOptions {
+            model_format: ModelFormat::Binary,
+            ..ExportOptions::default()
+        };
+        process_instructions(&tree, &mut binary_vfs, &options)
+            .expect("couldn't process instructions");
+
+        let mut xml_paths = Vec::new();
+        model_agnostic_paths(&vfs, "", &mut xml_paths);
+        let mut binary_paths = Vec::new();
+        model_agnostic_paths(&binary_vfs, "", &mut binary_paths);
+        assert_eq!(
+            xml_paths, binary_paths,
+            "binary models differ from xml in {:?}",
+            path
+        );
+
         let filesystem_path = path.join("filesystem");
         if let Err(error) = fs::remove_dir_all(&filesystem_path) {
             match error.kind() {
@@ -209,6 +423,1013 @@ fn run_tests() {
         fs::create_dir(&filesystem_path).unwrap();
 
         let mut filesystem = FileSystem::from_root(filesystem_path);
-        process_instructions(&tree, &mut filesystem, ExportMode::Full);
+        process_instructions(&tree, &mut filesystem, &ExportOptions::default())
+            .expect("couldn't write to the filesystem");
     }
 }
+
+// Run with `cargo test -- --ignored` where Rojo is installed
+#[test]
+#[ignore = "needs rojo on the PATH"]
+fn round_trips_build_with_rojo() {
+    let rojo = find_rojo().expect("rojo isn't on the PATH");
+
+    for fixture in &ROUND_TRIP_FIXTURES {
+        let path = Path::new("./test-files").join(fixture);
+        let source =
+            fs::read_to_string(path.join("source.rbxmx")).expect("couldn't read source.rbxmx");
+        let tree = rbx_xml::from_str_default(&source).expect("couldn't deserialize source.rbxmx");
+
+        assert_fixture_round_trips(&path, &tree, &output_variants(), Some(&rojo));
+    }
+}
+
+#[test]
+fn containers_meta_files_cant_hold_stay_models() {
+    let source = fs::read_to_string("test-files/containers/source.rbxmx")
+        .expect("couldn't read source.rbxmx");
+    let tree = rbx_xml::from_str_default(&source).expect("couldn't deserialize source.rbxmx");
+
+    let options = ExportOptions {
+        containers_as_folders: true,
+        ..ExportOptions::default()
+    };
+
+    let mut dry_run = DryRun::new();
+    process_instructions(&tree, &mut dry_run, &options).expect("couldn't process instructions");
+    let entries = dry_run.entries();
+
+    // Car's PrimaryPart can't go in a meta file, so it has to keep its model, and a meta
+    // file giving its class as well would make Rojo build it twice over
+    let car = Path::new("src/ReplicatedStorage/Car");
+    assert!(entries.contains_key(&car.join("Car.rbxmx")));
+    assert!(entries.contains_key(&car.join("default.project.json")));
+    assert!(!entries.contains_key(&car.join("init.meta.json")));
+
+    let sword = Path::new("src/ReplicatedStorage/Sword");
+    assert!(!entries.contains_key(&sword.join("Sword.rbxmx")));
+    match entries.get(&sword.join("init.meta.json")) {
+        Some(PlannedEntry::File(contents)) => {
+            let meta: MetaFile = serde_json::from_slice(contents).expect("couldn't parse meta");
+            assert_eq!(meta.class_name.as_deref(), Some("Tool"));
+        }
+
+        other => panic!("Sword has no init.meta.json: {:?}", other),
+    }
+}
+
+#[test]
+fn filesystem_errors_name_the_instance() {
+    let mut tree = WeakDom::new(InstanceBuilder::new("DataModel"));
+    tree.insert(
+        tree.root_ref(),
+        InstanceBuilder::new("ReplicatedStorage").with_child(
+            InstanceBuilder::new("ModuleScript")
+                .with_name("Module")
+                .with_property("Source", String::from("return {}")),
+        ),
+    );
+
+    let root = std::env::temp_dir().join("rbxlx-to-rojo-unwritable");
+    fs::remove_dir_all(&root).ok();
+    fs::create_dir_all(&root).unwrap();
+
+    // A file where the source folder should be makes every write fail
+    fs::write(root.join("src"), "").unwrap();
+
+    let mut filesystem = FileSystem::from_root(root.clone());
+    let error = process_instructions(&tree, &mut filesystem, &ExportOptions::default())
+        .expect_err("writing into a file should fail");
+
+    assert_eq!(error.operation(), Operation::CreateFolder);
+    assert_eq!(error.path(), root.join("src").join("ReplicatedStorage"));
+    assert_eq!(error.instance(), Some("ReplicatedStorage"));
+
+    fs::remove_dir_all(&root).ok();
+}
+
+#[test]
+fn dry_run_plans_without_writing() {
+    let mut tree = WeakDom::new(InstanceBuilder::new("DataModel"));
+    let storage = tree.insert(
+        tree.root_ref(),
+        InstanceBuilder::new("ReplicatedStorage").with_child(
+            InstanceBuilder::new("ModuleScript")
+                .with_name("Module")
+                .with_property("Source", String::from("return {}")),
+        ),
+    );
+    let module = tree.get_by_ref(storage).unwrap().children()[0];
+    let http_service = tree.insert(tree.root_ref(), InstanceBuilder::new("HttpService"));
+
+    let mut dry_run = DryRun::new();
+    let report = process_instructions(&tree, &mut dry_run, &ExportOptions::default())
+        .expect("couldn't process instructions");
+
+    assert_eq!(
+        dry_run
+            .entries()
+            .get(Path::new("src/ReplicatedStorage/Module.luau")),
+        Some(&PlannedEntry::File(b"return {}".to_vec()))
+    );
+    assert!(dry_run
+        .project_json()
+        .expect("no project file")
+        .contains("src/ReplicatedStorage"));
+    assert!(dry_run
+        .tree()
+        .ends_with("src/\n  ReplicatedStorage/\n    Module.luau (9 B)\n"));
+
+    assert_eq!(
+        report.instances,
+        vec![
+            InstanceReport {
+                instance: "ReplicatedStorage".to_string(),
+                referent: storage,
+                class_name: "ReplicatedStorage".to_string(),
+                decision: Decision::Exported,
+                reason: None,
+                path: Some(PathBuf::from("ReplicatedStorage")),
+            },
+            InstanceReport {
+                instance: "ReplicatedStorage.Module".to_string(),
+                referent: module,
+                class_name: "ModuleScript".to_string(),
+                decision: Decision::Exported,
+                reason: None,
+                path: Some(PathBuf::from("ReplicatedStorage/Module.luau")),
+            },
+            InstanceReport {
+                instance: "HttpService".to_string(),
+                referent: http_service,
+                class_name: "HttpService".to_string(),
+                decision: Decision::Skipped,
+                reason: Some("service isn't exported".to_string()),
+                path: None,
+            },
+        ]
+    );
+}
+
+#[test]
+fn updates_only_touch_changed_files() {
+    fn place(sources: &[(&str, &str)]) -> WeakDom {
+        let mut tree = WeakDom::new(InstanceBuilder::new("DataModel"));
+        let storage = tree.insert(tree.root_ref(), InstanceBuilder::new("ReplicatedStorage"));
+        for (name, source) in sources {
+            tree.insert(
+                storage,
+                InstanceBuilder::new("ModuleScript")
+                    .with_name(*name)
+                    .with_property("Source", source.to_string()),
+            );
+        }
+        tree
+    }
+
+    let root = std::env::temp_dir().join("rbxlx-to-rojo-update");
+    fs::remove_dir_all(&root).ok();
+    fs::create_dir_all(&root).unwrap();
+
+    let before = place(&[
+        ("Kept", "return 1"),
+        ("Changed", "return 2"),
+        ("Removed", "return 3"),
+    ]);
+    process_instructions(
+        &before,
+        &mut FileSystem::from_root(root.clone()),
+        &ExportOptions::default(),
+    )
+    .expect("couldn't write the first export");
+
+    let storage = root.join("src").join("ReplicatedStorage");
+    fs::write(storage.join("Handwritten.luau"), "return 4").unwrap();
+    let kept_modified = fs::metadata(storage.join("Kept.luau"))
+        .unwrap()
+        .modified()
+        .unwrap();
+
+    let after = place(&[
+        ("Kept", "return 1"),
+        ("Changed", "return 5"),
+        ("Handwritten", "return 6"),
+    ]);
+    let mut filesystem = FileSystem::update_root(root.clone()).expect("couldn't read the manifest");
+    process_instructions(&after, &mut filesystem, &ExportOptions::default())
+        .expect("couldn't update the export");
+
+    assert_eq!(
+        fs::metadata(storage.join("Kept.luau"))
+            .unwrap()
+            .modified()
+            .unwrap(),
+        kept_modified,
+        "unchanged file was rewritten"
+    );
+    assert_eq!(
+        fs::read_to_string(storage.join("Changed.luau")).unwrap(),
+        "return 5"
+    );
+    assert!(
+        !storage.join("Removed.luau").exists(),
+        "stale file wasn't removed"
+    );
+    assert_eq!(
+        fs::read_to_string(storage.join("Handwritten.luau")).unwrap(),
+        "return 4",
+        "file that wasn't exported before was overwritten"
+    );
+
+    fs::remove_dir_all(&root).ok();
+}
+
+#[test]
+fn check_reports_drift() {
+    fn place(source: &str, extra: bool) -> WeakDom {
+        let mut tree = WeakDom::new(InstanceBuilder::new("DataModel"));
+        let workspace = tree.insert(tree.root_ref(), InstanceBuilder::new("Workspace"));
+        tree.insert(
+            workspace,
+            InstanceBuilder::new("Model")
+                .with_name("Door")
+                .with_child(InstanceBuilder::new("Part").with_name("Frame"))
+                .with_child(
+                    InstanceBuilder::new("Script")
+                        .with_name("Open")
+                        .with_property("Source", source.to_string()),
+                ),
+        );
+
+        if extra {
+            tree.insert(
+                workspace,
+                InstanceBuilder::new("ModuleScript")
+                    .with_name("Extra")
+                    .with_property("Source", String::from("return nil")),
+            );
+        }
+
+        tree
+    }
+
+    let root = std::env::temp_dir().join("rbxlx-to-rojo-check");
+    fs::remove_dir_all(&root).ok();
+    fs::create_dir_all(&root).unwrap();
+
+    let options = ExportOptions::default();
+    process_instructions(
+        &place("print(1)\n", true),
+        &mut FileSystem::from_root(root.clone()),
+        &options,
+    )
+    .expect("couldn't write the project");
+
+    // Models are written with new referents every time, which mustn't count as drift
+    let up_to_date = check_project(&place("print(1)\n", true), &root, &options)
+        .expect("couldn't check the project");
+    assert!(up_to_date.is_empty(), "unexpected drift: {}", up_to_date);
+
+    let drift = check_project(&place("print(2)\n", false), &root, &options)
+        .expect("couldn't check the project");
+    let changes: Vec<_> = drift
+        .changes
+        .iter()
+        .map(|change| (change.path.clone(), change.kind))
+        .collect();
+
+    assert!(changes.contains(&(
+        PathBuf::from("src/Workspace/Door/Open.server.luau"),
+        ChangeKind::Modified
+    )));
+    assert!(changes.contains(&(
+        PathBuf::from("src/Workspace/Extra.luau"),
+        ChangeKind::Removed
+    )));
+
+    let diff = drift
+        .changes
+        .iter()
+        .find(|change| change.kind == ChangeKind::Modified && change.diff.is_some())
+        .and_then(|change| change.diff.as_deref())
+        .unwrap();
+    assert!(
+        diff.contains("-print(1)\n+print(2)"),
+        "unexpected diff: {}",
+        diff
+    );
+
+    // A broken manifest isn't mistaken for a missing one
+    fs::write(root.join("rbxlx-to-rojo.manifest.json"), "{").unwrap();
+    let error = check_project(&place("print(2)\n", false), &root, &options)
+        .expect_err("a broken manifest wasn't reported");
+    assert_eq!(error.operation(), Operation::ReadManifest);
+
+    fs::remove_dir_all(&root).ok();
+}
+
+#[test]
+fn verify_round_trips_a_project() {
+    let mut tree = WeakDom::new(InstanceBuilder::new("DataModel"));
+    let root_ref = tree.root_ref();
+    tree.insert(
+        root_ref,
+        InstanceBuilder::new("ReplicatedStorage").with_child(
+            InstanceBuilder::new("Folder")
+                .with_name("Shared/Stuff")
+                .with_child(
+                    InstanceBuilder::new("ModuleScript")
+                        .with_name("Util")
+                        .with_property("Source", String::from("return {}")),
+                )
+                .with_child(
+                    InstanceBuilder::new("StringValue")
+                        .with_name("Greeting")
+                        .with_property("Value", String::from("hi")),
+                ),
+        ),
+    );
+    tree.insert(
+        root_ref,
+        InstanceBuilder::new("ServerScriptService").with_child(
+            InstanceBuilder::new("Script")
+                .with_name("Main")
+                .with_property("Source", String::from("require(script.Helper)"))
+                .with_child(
+                    InstanceBuilder::new("ModuleScript")
+                        .with_name("Helper")
+                        .with_property("Source", String::from("return nil")),
+                ),
+        ),
+    );
+    tree.insert(
+        root_ref,
+        InstanceBuilder::new("Workspace").with_child(
+            InstanceBuilder::new("Model")
+                .with_name("Door")
+                .with_child(InstanceBuilder::new("Part").with_name("Frame"))
+                .with_child(
+                    InstanceBuilder::new("Script")
+                        .with_name("Open")
+                        .with_property("Source", String::from("print(1)")),
+                ),
+        ),
+    );
+
+    let root = std::env::temp_dir().join("rbxlx-to-rojo-verify");
+    fs::remove_dir_all(&root).ok();
+    fs::create_dir_all(&root).unwrap();
+
+    let options = ExportOptions::default();
+    process_instructions(&tree, &mut FileSystem::from_root(root.clone()), &options)
+        .expect("couldn't write the project");
+
+    let verification = verify_project(&tree, &root, &options).expect("couldn't verify");
+    assert!(
+        verification.is_empty(),
+        "unexpected mismatches: {}",
+        verification
+    );
+
+    let stuff = root.join("src/ReplicatedStorage/Shared_Stuff");
+    fs::remove_file(stuff.join("Greeting.txt")).unwrap();
+    fs::write(stuff.join("Util.luau"), "return nil").unwrap();
+
+    let verification = verify_project(&tree, &root, &options).expect("couldn't verify");
+    let mismatches: Vec<_> = verification
+        .mismatches
+        .iter()
+        .map(|mismatch| (mismatch.instance.as_str(), &mismatch.kind))
+        .collect();
+
+    assert_eq!(
+        mismatches,
+        vec![
+            (
+                "ReplicatedStorage.Shared/Stuff.Util",
+                &MismatchKind::PropertyChanged {
+                    property: "Source".to_string(),
+                    expected: Some("\"return {}\"".to_string()),
+                    actual: Some("\"return nil\"".to_string()),
+                }
+            ),
+            (
+                "ReplicatedStorage.Shared/Stuff.Greeting",
+                &MismatchKind::Missing {
+                    class_name: "StringValue".to_string(),
+                }
+            ),
+        ]
+    );
+
+    // Rojo would build a module from this, which an export never writes
+    fs::write(stuff.join("Data.json"), "{}").unwrap();
+    let error = verify_project(&tree, &root, &options).expect_err("plain JSON should fail");
+    assert_eq!(error.path(), stuff.join("Data.json"));
+    fs::remove_file(stuff.join("Data.json")).unwrap();
+
+    // Rojo builds an init model as a child named `init`, not as its folder's instance
+    let door = root.join("src/Workspace/Door");
+    fs::remove_file(door.join("default.project.json")).unwrap();
+    fs::rename(door.join("Door.rbxmx"), door.join("init.rbxmx")).unwrap();
+
+    let verification = verify_project(&tree, &root, &options).expect("couldn't verify");
+    let mismatches: Vec<_> = verification
+        .mismatches
+        .iter()
+        .map(|mismatch| (mismatch.instance.as_str(), &mismatch.kind))
+        .collect();
+
+    assert!(mismatches.contains(&(
+        "Workspace.Door",
+        &MismatchKind::ClassChanged {
+            expected: "Model".to_string(),
+            actual: "Folder".to_string(),
+        }
+    )));
+    assert!(mismatches.contains(&(
+        "Workspace.Door.init",
+        &MismatchKind::Unexpected {
+            class_name: "Model".to_string(),
+        }
+    )));
+
+    fs::remove_dir_all(&root).ok();
+}
+
+#[test]
+fn verify_tells_skipped_instances_from_their_namesakes() {
+    let mut tree = WeakDom::new(InstanceBuilder::new("DataModel"));
+    tree.insert(
+        tree.root_ref(),
+        InstanceBuilder::new("ReplicatedStorage")
+            .with_child(InstanceBuilder::new("Folder").with_name("Thing"))
+            .with_child(
+                InstanceBuilder::new("Folder")
+                    .with_name("Thing")
+                    .with_child(
+                        InstanceBuilder::new("ModuleScript")
+                            .with_name("Util")
+                            .with_property("Source", String::from("return {}")),
+                    ),
+            ),
+    );
+
+    let root = std::env::temp_dir().join("rbxlx-to-rojo-verify-namesakes");
+    fs::remove_dir_all(&root).ok();
+    fs::create_dir_all(&root).unwrap();
+
+    let options = ExportOptions {
+        mode: ExportMode::ScriptsOnly,
+        ..ExportOptions::default()
+    };
+    let report = process_instructions(&tree, &mut FileSystem::from_root(root.clone()), &options)
+        .expect("couldn't write the project");
+
+    let written = report
+        .with_decision(Decision::Exported)
+        .find(|entry| entry.instance == "ReplicatedStorage.Thing")
+        .and_then(|entry| entry.path.clone())
+        .expect("the folder with a script wasn't written");
+    fs::remove_dir_all(root.join("src").join(written)).unwrap();
+
+    // Only the empty folder was skipped, the one with a script has to be reported missing
+    let verification = verify_project(&tree, &root, &options).expect("couldn't verify");
+    let mismatches: Vec<_> = verification
+        .mismatches
+        .iter()
+        .map(|mismatch| (mismatch.instance.as_str(), &mismatch.kind))
+        .collect();
+
+    assert_eq!(
+        mismatches,
+        vec![(
+            "ReplicatedStorage.Thing",
+            &MismatchKind::Missing {
+                class_name: "Folder".to_string(),
+            }
+        )]
+    );
+
+    fs::remove_dir_all(&root).ok();
+}
+
+#[test]
+fn subtrees_are_exported_to_their_place_in_the_tree() {
+    let mut tree = WeakDom::new(InstanceBuilder::new("DataModel"));
+    let root_ref = tree.root_ref();
+    tree.insert(
+        root_ref,
+        InstanceBuilder::new("ServerScriptService")
+            .with_child(
+                InstanceBuilder::new("Folder")
+                    .with_name("Combat")
+                    .with_child(
+                        InstanceBuilder::new("Script")
+                            .with_name("Hit")
+                            .with_property("Source", String::from("print(1)")),
+                    ),
+            )
+            .with_child(
+                InstanceBuilder::new("Script")
+                    .with_name("Other")
+                    .with_property("Source", String::from("print(2)")),
+            ),
+    );
+    tree.insert(
+        root_ref,
+        InstanceBuilder::new("ReplicatedStorage").with_child(
+            InstanceBuilder::new("Folder")
+                .with_name("Shared")
+                .with_child(
+                    InstanceBuilder::new("ModuleScript")
+                        .with_name("Util")
+                        .with_property("Source", String::from("return {}")),
+                ),
+        ),
+    );
+
+    let options = ExportOptions {
+        subtrees: vec![
+            "ServerScriptService.Combat".to_string(),
+            "ReplicatedStorage.Shared.Util".to_string(),
+        ],
+        ..ExportOptions::default()
+    };
+
+    let mut dry_run = DryRun::new();
+    let report =
+        process_instructions(&tree, &mut dry_run, &options).expect("couldn't process instructions");
+
+    let files: Vec<_> = dry_run
+        .entries()
+        .iter()
+        .filter(|(_, entry)| matches!(entry, PlannedEntry::File(_)))
+        .map(|(path, _)| path.to_string_lossy().replace("\\", "/"))
+        .collect();
+    assert_eq!(
+        files,
+        vec![
+            "default.project.json",
+            "src/ReplicatedStorage/Shared/Util.luau",
+            "src/ServerScriptService/Combat/Hit.server.luau",
+            "src/ServerScriptService/Combat/init.meta.json",
+        ]
+    );
+
+    let project: serde_json::Value =
+        serde_json::from_str(dry_run.project_json().expect("no project file")).unwrap();
+    assert_eq!(
+        project["tree"]["ServerScriptService"]["Combat"]["$path"],
+        "src/ServerScriptService/Combat"
+    );
+    assert_eq!(
+        project["tree"]["ReplicatedStorage"]["Shared"]["$className"],
+        "Folder"
+    );
+    assert_eq!(
+        project["tree"]["ReplicatedStorage"]["Shared"]["Util"]["$path"],
+        "src/ReplicatedStorage/Shared/Util.luau"
+    );
+
+    let skipped: Vec<_> = report
+        .with_decision(Decision::Skipped)
+        .map(|entry| entry.instance.as_str())
+        .collect();
+    assert_eq!(skipped, vec!["ServerScriptService.Other"]);
+
+    // Models have no services, so a single subtree becomes the root of the project
+    let mut model = WeakDom::new(InstanceBuilder::new("DataModel"));
+    model.insert(
+        model.root_ref(),
+        InstanceBuilder::new("Tool").with_name("Sword").with_child(
+            InstanceBuilder::new("Script")
+                .with_name("Swing")
+                .with_property("Source", String::from("print(3)")),
+        ),
+    );
+
+    let options = ExportOptions {
+        subtrees: vec!["Sword".to_string()],
+        ..ExportOptions::default()
+    };
+
+    let mut dry_run = DryRun::new();
+    process_instructions(&model, &mut dry_run, &options).expect("couldn't process instructions");
+
+    let project: serde_json::Value =
+        serde_json::from_str(dry_run.project_json().expect("no project file")).unwrap();
+    assert_eq!(project["name"], "Sword");
+    assert_eq!(project["tree"]["$className"], "Tool");
+    assert_eq!(project["tree"]["$path"], "src/Sword");
+
+    // Several of them go under a Folder, which Rojo builds as the model's root
+    model.insert(
+        model.root_ref(),
+        InstanceBuilder::new("Tool").with_name("Shield").with_child(
+            InstanceBuilder::new("Script")
+                .with_name("Block")
+                .with_property("Source", String::from("print(4)")),
+        ),
+    );
+
+    let options = ExportOptions {
+        subtrees: vec!["Sword".to_string(), "Shield".to_string()],
+        ..ExportOptions::default()
+    };
+
+    let mut dry_run = DryRun::new();
+    process_instructions(&model, &mut dry_run, &options).expect("couldn't process instructions");
+
+    let project: serde_json::Value =
+        serde_json::from_str(dry_run.project_json().expect("no project file")).unwrap();
+    assert_eq!(project["tree"]["$className"], "Folder");
+    assert_eq!(project["tree"]["Sword"]["$path"], "src/Sword");
+    assert_eq!(project["tree"]["Shield"]["$path"], "src/Shield");
+
+    let options = ExportOptions {
+        subtrees: vec!["Sword.Missing".to_string()],
+        ..ExportOptions::default()
+    };
+
+    let error = process_instructions(&model, &mut DryRun::new(), &options)
+        .expect_err("a missing subtree wasn't reported");
+    assert_eq!(error.operation(), Operation::FindInstance);
+    assert!(matches!(error.cause(), Cause::InstanceNotFound));
+    assert_eq!(error.path(), Path::new("Sword.Missing"));
+}
+
+#[test]
+fn filters_leave_out_instances() {
+    let mut tree = WeakDom::new(InstanceBuilder::new("DataModel"));
+    let root_ref = tree.root_ref();
+    tree.insert(
+        root_ref,
+        InstanceBuilder::new("Workspace")
+            .with_child(InstanceBuilder::new("Camera"))
+            .with_child(
+                InstanceBuilder::new("Model")
+                    .with_name("Map")
+                    .with_child(InstanceBuilder::new("Part").with_name("Floor"))
+                    .with_child(
+                        InstanceBuilder::new("Folder")
+                            .with_name("ThirdParty")
+                            .with_child(InstanceBuilder::new("Part").with_name("Tree")),
+                    ),
+            )
+            .with_child(InstanceBuilder::new("Part").with_name("Baseplate")),
+    );
+    tree.insert(
+        root_ref,
+        InstanceBuilder::new("ReplicatedStorage").with_child(
+            InstanceBuilder::new("Folder")
+                .with_name("ThirdParty")
+                .with_child(
+                    InstanceBuilder::new("ModuleScript")
+                        .with_name("Library")
+                        .with_property("Source", String::from("return {}")),
+                ),
+        ),
+    );
+
+    let filtered = |report: &Report| -> Vec<String> {
+        let mut filtered: Vec<_> = report
+            .with_decision(Decision::Skipped)
+            .filter(|entry| entry.reason.as_deref() == Some("left out by the filters"))
+            .map(|entry| entry.instance.clone())
+            .collect();
+        filtered.sort();
+        filtered
+    };
+
+    let options = ExportOptions {
+        filters: Filters {
+            exclude: vec!["**/ThirdParty/**".to_string()],
+            exclude_classes: vec!["Camera".to_string()],
+            ..Filters::default()
+        },
+        ..ExportOptions::default()
+    };
+
+    let mut dry_run = DryRun::new();
+    let report =
+        process_instructions(&tree, &mut dry_run, &options).expect("couldn't process instructions");
+
+    assert_eq!(
+        filtered(&report),
+        vec![
+            "ReplicatedStorage.ThirdParty",
+            "Workspace.Camera",
+            "Workspace.Map.ThirdParty",
+        ]
+    );
+
+    let files: Vec<_> = dry_run
+        .entries()
+        .keys()
+        .map(|path| path.to_string_lossy().replace("\\", "/"))
+        .collect();
+    assert!(!files.iter().any(|file| file.contains("ThirdParty")));
+    assert!(!files.iter().any(|file| file.contains("Camera")));
+
+    // Nor are they in the model they would have been packed into
+    let map_file = match &dry_run.entries()[Path::new("src/Workspace/Map.rbxmx")] {
+        PlannedEntry::File(contents) => contents,
+        PlannedEntry::Folder => panic!("the map was written as a folder"),
+    };
+    let map_model: WeakDom = rbx_xml::from_reader_default(map_file.as_slice()).unwrap();
+    let map = map_model
+        .get_by_ref(map_model.root().children()[0])
+        .unwrap();
+    assert_eq!(model_children(&map_model, map), vec!["Floor"]);
+
+    let options = ExportOptions {
+        filters: Filters {
+            include: vec!["Workspace.Map.**".to_string()],
+            ..Filters::default()
+        },
+        ..ExportOptions::default()
+    };
+
+    let mut dry_run = DryRun::new();
+    let report =
+        process_instructions(&tree, &mut dry_run, &options).expect("couldn't process instructions");
+
+    assert_eq!(
+        filtered(&report),
+        vec![
+            "ReplicatedStorage",
+            "Workspace.Baseplate",
+            "Workspace.Camera",
+        ]
+    );
+}
+
+#[test]
+fn config_file_sets_up_the_export() {
+    let root = std::env::temp_dir().join("rbxlx-to-rojo-config");
+    fs::remove_dir_all(&root).ok();
+    fs::create_dir_all(&root).unwrap();
+    fs::write(
+        root.join(CONFIG_FILE),
+        r#"
+mode = "scripts-only"
+script_extension = "lua"
+respected_services = ["ReplicatedStorage"]
+
+[project]
+name = "game"
+
+[filters]
+exclude = ["**/ThirdParty/**"]
+"#,
+    )
+    .unwrap();
+
+    assert_eq!(Config::find([Path::new("does-not-exist")]).unwrap(), None);
+
+    let (path, config) = Config::find([Path::new("does-not-exist"), root.as_path()])
+        .unwrap()
+        .expect("the config wasn't found");
+    assert_eq!(path, root.join(CONFIG_FILE));
+
+    let options = config.options();
+    assert_eq!(options.mode, ExportMode::ScriptsOnly);
+    assert_eq!(options.script_extension, ScriptExtension::Lua);
+    assert_eq!(options.model_format, ModelFormat::Xml);
+    assert_eq!(options.filters.exclude, vec!["**/ThirdParty/**"]);
+
+    let mut tree = WeakDom::new(InstanceBuilder::new("DataModel"));
+    let root_ref = tree.root_ref();
+    for service in &["ReplicatedStorage", "ServerScriptService"] {
+        tree.insert(
+            root_ref,
+            InstanceBuilder::new(*service).with_child(
+                InstanceBuilder::new("ModuleScript")
+                    .with_name("Module")
+                    .with_property("Source", String::from("return {}")),
+            ),
+        );
+    }
+
+    let mut dry_run = DryRun::new();
+    let report =
+        process_instructions(&tree, &mut dry_run, &options).expect("couldn't process instructions");
+
+    assert!(dry_run
+        .entries()
+        .contains_key(Path::new("src/ReplicatedStorage/Module.lua")));
+    assert!(report
+        .with_decision(Decision::Skipped)
+        .any(|entry| entry.instance == "ServerScriptService"));
+
+    let project: serde_json::Value =
+        serde_json::from_str(dry_run.project_json().expect("no project file")).unwrap();
+    assert_eq!(project["name"], "game");
+
+    fs::write(root.join(CONFIG_FILE), "made_up_setting = true").unwrap();
+    let error = Config::find([root.as_path()]).expect_err("an unknown setting was accepted");
+    assert_eq!(error.operation(), Operation::ReadFile);
+
+    fs::remove_dir_all(&root).ok();
+}
+
+#[test]
+fn project_file_has_its_settings() {
+    let mut tree = WeakDom::new(InstanceBuilder::new("DataModel"));
+    tree.insert(
+        tree.root_ref(),
+        InstanceBuilder::new("ReplicatedStorage").with_child(
+            InstanceBuilder::new("ModuleScript")
+                .with_name("Module")
+                .with_property("Source", String::from("return {}")),
+        ),
+    );
+
+    let mut dry_run = DryRun::new();
+    process_instructions(&tree, &mut dry_run, &ExportOptions::default())
+        .expect("couldn't process instructions");
+
+    let project: serde_json::Value =
+        serde_json::from_str(dry_run.project_json().expect("no project file")).unwrap();
+    assert_eq!(project["name"], "project");
+    assert!(project.get("servePort").is_none());
+    assert!(project.get("globIgnorePaths").is_none());
+
+    let options = ExportOptions {
+        project: ProjectOptions {
+            name: Some("game".to_string()),
+            serve_port: Some(34873),
+            serve_place_ids: vec![1818],
+            place_id: Some(1818),
+            game_id: Some(7),
+            glob_ignore_paths: vec!["**/*.spec.luau".to_string()],
+        },
+        ..ExportOptions::default()
+    };
+
+    let mut dry_run = DryRun::new();
+    process_instructions(&tree, &mut dry_run, &options).expect("couldn't process instructions");
+
+    let project: serde_json::Value =
+        serde_json::from_str(dry_run.project_json().expect("no project file")).unwrap();
+    assert_eq!(project["name"], "game");
+    assert_eq!(project["servePort"], 34873);
+    assert_eq!(project["servePlaceIds"], serde_json::json!([1818]));
+    assert_eq!(project["placeId"], 1818);
+    assert_eq!(project["gameId"], 7);
+    assert_eq!(
+        project["globIgnorePaths"],
+        serde_json::json!(["**/*.spec.luau"])
+    );
+    assert_eq!(project["tree"]["$className"], "DataModel");
+
+    // Rojo names a model after its project, so the model's own name has to win
+    let mut model = WeakDom::new(InstanceBuilder::new("DataModel"));
+    model.insert(
+        model.root_ref(),
+        InstanceBuilder::new("Tool").with_name("Sword").with_child(
+            InstanceBuilder::new("Script")
+                .with_name("Swing")
+                .with_property("Source", String::from("print(3)")),
+        ),
+    );
+
+    let options = ExportOptions {
+        subtrees: vec!["Sword".to_string()],
+        ..options
+    };
+
+    let mut dry_run = DryRun::new();
+    process_instructions(&model, &mut dry_run, &options).expect("couldn't process instructions");
+
+    let project: serde_json::Value =
+        serde_json::from_str(dry_run.project_json().expect("no project file")).unwrap();
+    assert_eq!(project["name"], "Sword");
+    assert_eq!(project["servePort"], 34873);
+}
+
+#[test]
+fn service_properties_are_kept_in_the_tree() {
+    let db = rbx_reflection_database::get().expect("couldn't get reflection database");
+    let default_fog_start = db.classes["Lighting"].default_properties["FogStart"].clone();
+
+    let mut tree = WeakDom::new(InstanceBuilder::new("DataModel"));
+    let root_ref = tree.root_ref();
+    tree.insert(
+        root_ref,
+        InstanceBuilder::new("Lighting")
+            .with_property("FogEnd", 500.0f32)
+            .with_property("FogStart", default_fog_start),
+    );
+    tree.insert(
+        root_ref,
+        InstanceBuilder::new("Workspace").with_property("Gravity", 100.0f32),
+    );
+    tree.insert(
+        root_ref,
+        InstanceBuilder::new("Players").with_property("CharacterAutoLoads", false),
+    );
+    tree.insert(
+        root_ref,
+        InstanceBuilder::new("HttpService").with_property("HttpEnabled", true),
+    );
+
+    let mut dry_run = DryRun::new();
+    let report = process_instructions(&tree, &mut dry_run, &ExportOptions::default())
+        .expect("couldn't process instructions");
+
+    let project: serde_json::Value =
+        serde_json::from_str(dry_run.project_json().expect("no project file")).unwrap();
+    let project_tree = &project["tree"];
+
+    assert_eq!(
+        project_tree["Lighting"]["$properties"],
+        serde_json::json!({ "FogEnd": { "Float32": 500.0 } })
+    );
+    assert_eq!(
+        project_tree["Workspace"]["$properties"]["Gravity"],
+        serde_json::json!({ "Float32": 100.0 })
+    );
+
+    // Players isn't exported, but its settings are
+    assert_eq!(
+        project_tree["Players"]["$properties"],
+        serde_json::json!({ "CharacterAutoLoads": { "Bool": false } })
+    );
+    assert!(project_tree["Players"].get("$path").is_none());
+
+    // Only the services listed in `settings_services` are kept for their settings
+    assert!(project_tree.get("HttpService").is_none());
+
+    let players = report
+        .instances
+        .iter()
+        .find(|entry| entry.instance == "Players")
+        .expect("Players isn't in the report");
+    assert_eq!(players.decision, Decision::Exported);
+    assert_eq!(players.path, None);
+}
+
+#[test]
+fn property_values_round_trip_through_json() {
+    let values = [
+        Variant::Font(Font::new(
+            "rbxasset://fonts/families/GothamSSm.json",
+            FontWeight::Bold,
+            FontStyle::Italic,
+        )),
+        Variant::PhysicalProperties(PhysicalProperties::Default),
+        Variant::PhysicalProperties(PhysicalProperties::Custom(CustomPhysicalProperties {
+            density: 0.5,
+            friction: 0.25,
+            elasticity: 1.0,
+            friction_weight: 2.0,
+            elasticity_weight: 0.75,
+        })),
+        Variant::Faces(Faces::TOP | Faces::FRONT),
+        Variant::Axes(Axes::X | Axes::Z),
+        Variant::Content(Content::from_uri("rbxassetid://1818")),
+        Variant::OptionalCFrame(None),
+    ];
+
+    for value in &values {
+        let encoded = encode_variant(value).expect("couldn't encode");
+        assert_eq!(
+            decode_variant(&encoded).as_ref(),
+            Some(value),
+            "{}",
+            encoded
+        );
+    }
+
+    assert_eq!(
+        encode_variant(&values[0]).unwrap(),
+        serde_json::json!({
+            "Font": {
+                "family": "rbxasset://fonts/families/GothamSSm.json",
+                "weight": "Bold",
+                "style": "Italic",
+            }
+        })
+    );
+
+    // JSON has no NaN or infinity, serde_json would quietly write them as null
+    assert_eq!(
+        encode_variant(&Variant::Float32(f32::NAN)),
+        Err(EncodeError::NonFinite)
+    );
+    assert_eq!(
+        encode_variant(&Variant::Vector3(Vector3::new(0.0, f32::INFINITY, 0.0))),
+        Err(EncodeError::NonFinite)
+    );
+}
+
+/// The names of an instance's children in a decoded model.
+fn model_children(model: &WeakDom, instance: &rbx_dom_weak::Instance) -> Vec<String> {
+    instance
+        .children()
+        .iter()
+        .map(|child| model.get_by_ref(*child).unwrap().name.clone())
+        .collect()
+}
@@ -0,0 +1,940 @@
+use crate::{
+    dry_run::DryRun,
+    error::{Error, Operation},
+    filesystem::PROJECT_FILE,
+    full_name, process_instructions,
+    properties::{comparable_properties, decode_attributes, decode_variant, Properties},
+    report::Decision,
+    structures::*,
+    ExportOptions,
+};
+use log::{debug, warn};
+use rbx_dom_weak::{
+    types::{Ref, Variant},
+    Instance, InstanceBuilder, WeakDom,
+};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    env, fmt, fs, io,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// What Rojo builds from a file, going by its name.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum FileKind {
+    Script(&'static str),
+    Model,
+    JsonModel,
+    Text,
+    Localization,
+    /// Something Rojo builds an instance from that rbxlx-to-rojo never writes, so rebuilding
+    /// it here would only be a guess.
+    Unsupported,
+}
+
+/// Rojo's default sync rules, matched in order so the longest suffixes win. Files marked
+/// `None` don't become instances of their own.
+const FILE_KINDS: [(&str, Option<FileKind>); 15] = [
+    // `*.server.lua(u)` is a Script, `*.client.lua(u)` a LocalScript, and any other Lua file
+    // a ModuleScript, holding the file as its Source
+    (".server.luau", Some(FileKind::Script("Script"))),
+    (".server.lua", Some(FileKind::Script("Script"))),
+    (".client.luau", Some(FileKind::Script("LocalScript"))),
+    (".client.lua", Some(FileKind::Script("LocalScript"))),
+    (".luau", Some(FileKind::Script("ModuleScript"))),
+    (".lua", Some(FileKind::Script("ModuleScript"))),
+    // Meta files only add to the instance of the file or folder they're named after
+    (".meta.json", None),
+    // Nested projects
+    (".project.json", Some(FileKind::Unsupported)),
+    // A JSON model is named after its file, whatever its `name` says
+    (".model.json", Some(FileKind::JsonModel)),
+    // Any other JSON or TOML file is a ModuleScript returning its data
+    (".json", Some(FileKind::Unsupported)),
+    (".toml", Some(FileKind::Unsupported)),
+    // A model file has to hold exactly one instance, which is named after the file
+    (".rbxmx", Some(FileKind::Model)),
+    (".rbxm", Some(FileKind::Model)),
+    // A StringValue holding the text
+    (".txt", Some(FileKind::Text)),
+    // A LocalizationTable holding the rows, with a column per locale
+    (".csv", Some(FileKind::Localization)),
+];
+
+/// Splits a file name into the name of the instance Rojo builds from it and what kind of
+/// file it is, or `None` if Rojo doesn't build an instance from it at all.
+fn classify(file_name: &str) -> Option<(&str, FileKind)> {
+    FILE_KINDS.iter().find_map(|(suffix, kind)| {
+        let stem = file_name.strip_suffix(suffix)?;
+        Some(kind.map(|kind| (stem, kind)))
+    })?
+}
+
+/// Whether an `init` file of this kind makes its folder into the instance it describes.
+/// Rojo only does this for scripts and `init.csv`, any other `init` file, models included,
+/// becomes a child named `init`.
+fn is_folder_init(kind: FileKind) -> bool {
+    matches!(kind, FileKind::Script(_) | FileKind::Localization)
+}
+
+fn invalid_file(path: &Path, message: impl Into<String>) -> Error {
+    Error::new(
+        Operation::ReadFile,
+        path,
+        io::Error::new(io::ErrorKind::InvalidData, message.into()),
+    )
+}
+
+fn read_file(path: &Path) -> Result<Vec<u8>, Error> {
+    fs::read(path).map_err(|error| Error::new(Operation::ReadFile, path, error))
+}
+
+fn read_json<T: DeserializeOwned>(path: &Path) -> Result<T, Error> {
+    serde_json::from_slice(&read_file(path)?)
+        .map_err(|error| Error::new(Operation::ReadFile, path, error.into()))
+}
+
+fn parse_csv(text: &str) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if quoted {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => quoted = false,
+                c => field.push(c),
+            }
+
+            continue;
+        }
+
+        match c {
+            '"' => quoted = true,
+            ',' => row.push(std::mem::take(&mut field)),
+            '\r' => {}
+            '\n' => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            c => field.push(c),
+        }
+    }
+
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+
+    rows
+}
+
+/// Turns a localization CSV back into the JSON a LocalizationTable keeps in `Contents`.
+fn localization_contents(csv: &str) -> String {
+    let mut rows = parse_csv(csv).into_iter();
+    let header = rows.next().unwrap_or_default();
+
+    let entries: Vec<Value> = rows
+        .map(|row| {
+            let mut entry = serde_json::Map::new();
+            let mut values = serde_json::Map::new();
+
+            for (column, field) in header.iter().zip(row) {
+                if field.is_empty() {
+                    continue;
+                }
+
+                match column.as_str() {
+                    "Key" => entry.insert("key".to_string(), json!(field)),
+                    "Source" => entry.insert("source".to_string(), json!(field)),
+                    "Context" => entry.insert("context".to_string(), json!(field)),
+                    "Example" => entry.insert("example".to_string(), json!(field)),
+                    locale => values.insert(locale.to_string(), json!(field)),
+                };
+            }
+
+            entry.insert("values".to_string(), Value::Object(values));
+            Value::Object(entry)
+        })
+        .collect();
+
+    Value::Array(entries).to_string()
+}
+
+/// Rebuilds the instances a Rojo project describes, following the rules Rojo itself uses.
+struct Rebuilder {
+    dom: WeakDom,
+}
+
+impl Rebuilder {
+    fn set_property(&mut self, referent: Ref, name: &str, value: Variant) {
+        let instance = self
+            .dom
+            .get_by_ref_mut(referent)
+            .expect("got fake referent?");
+        instance.properties.insert(ustr::ustr(name), value);
+    }
+
+    fn apply_properties(
+        &mut self,
+        referent: Ref,
+        properties: &Properties,
+        attributes: &Properties,
+        source: &Path,
+    ) {
+        for (name, value) in properties {
+            match decode_variant(value) {
+                Some(value) => self.set_property(referent, name, value),
+                None => warn!(
+                    "couldn't decode property {} from {}, leaving it out",
+                    name,
+                    source.display()
+                ),
+            }
+        }
+
+        if !attributes.is_empty() {
+            self.set_property(
+                referent,
+                "Attributes",
+                Variant::Attributes(decode_attributes(attributes)),
+            );
+        }
+    }
+
+    fn apply_meta(&mut self, referent: Ref, meta: &MetaFile, source: &Path) {
+        if let Some(name) = &meta.name {
+            self.dom
+                .get_by_ref_mut(referent)
+                .expect("got fake referent?")
+                .name = name.clone();
+        }
+
+        self.apply_properties(referent, &meta.properties, &meta.attributes, source);
+    }
+
+    fn read_meta(&self, path: &Path) -> Result<Option<MetaFile>, Error> {
+        if path.is_file() {
+            read_json(path).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn insert_json_model(&mut self, parent: Ref, model: &ModelJson, name: &str) -> Ref {
+        let referent = self.dom.insert(
+            parent,
+            InstanceBuilder::new(model.class_name.as_str()).with_name(name),
+        );
+
+        self.apply_properties(
+            referent,
+            &model.properties,
+            &model.attributes,
+            Path::new(name),
+        );
+
+        for child in &model.children {
+            let child_name = child
+                .name
+                .clone()
+                .unwrap_or_else(|| child.class_name.clone());
+            self.insert_json_model(referent, child, &child_name);
+        }
+
+        referent
+    }
+
+    /// Builds the instance of one file under `parent`, named `name`.
+    fn load_file(
+        &mut self,
+        parent: Ref,
+        path: &Path,
+        kind: FileKind,
+        name: &str,
+    ) -> Result<Ref, Error> {
+        let referent = match kind {
+            FileKind::Script(class_name) => {
+                let source = String::from_utf8_lossy(&read_file(path)?).into_owned();
+                self.dom.insert(
+                    parent,
+                    InstanceBuilder::new(class_name)
+                        .with_name(name)
+                        .with_property("Source", source),
+                )
+            }
+
+            FileKind::Text => {
+                let value = String::from_utf8_lossy(&read_file(path)?).into_owned();
+                self.dom.insert(
+                    parent,
+                    InstanceBuilder::new("StringValue")
+                        .with_name(name)
+                        .with_property("Value", value),
+                )
+            }
+
+            FileKind::Localization => {
+                let csv = String::from_utf8_lossy(&read_file(path)?).into_owned();
+                self.dom.insert(
+                    parent,
+                    InstanceBuilder::new("LocalizationTable")
+                        .with_name(name)
+                        .with_property("Contents", localization_contents(&csv)),
+                )
+            }
+
+            FileKind::JsonModel => {
+                let model: ModelJson = read_json(path)?;
+                self.insert_json_model(parent, &model, name)
+            }
+
+            FileKind::Model => {
+                let contents = read_file(path)?;
+                let mut model = if path
+                    .extension()
+                    .map_or(false, |extension| extension == "rbxm")
+                {
+                    rbx_binary::from_reader(contents.as_slice())
+                        .map_err(|error| invalid_file(path, error.to_string()))?
+                } else {
+                    // Read the way places are read for exporting, properties are compared
+                    // by their canonical names either way
+                    let options = rbx_xml::DecodeOptions::new()
+                        .property_behavior(rbx_xml::DecodePropertyBehavior::NoReflection);
+                    rbx_xml::from_reader(contents.as_slice(), options)
+                        .map_err(|error| invalid_file(path, error.to_string()))?
+                };
+
+                let model_root = match model.root().children() {
+                    [model_root] => *model_root,
+                    roots => {
+                        return Err(invalid_file(
+                            path,
+                            format!(
+                                "has {} top-level instances, Rojo needs exactly one",
+                                roots.len()
+                            ),
+                        ))
+                    }
+                };
+
+                model.transfer(model_root, &mut self.dom, parent);
+                self.dom
+                    .get_by_ref_mut(model_root)
+                    .expect("model root went missing?")
+                    .name = name.to_string();
+                model_root
+            }
+
+            FileKind::Unsupported => {
+                return Err(invalid_file(
+                    path,
+                    "Rojo builds an instance from this, but rbxlx-to-rojo never writes one",
+                ))
+            }
+        };
+
+        Ok(referent)
+    }
+
+    /// Builds the instance of a folder under `parent`. A folder with a `default.project.json`
+    /// is built from that project alone. Otherwise, an `init` file makes the folder that
+    /// instance, as `is_folder_init` decides, and everything else in it becomes a child.
+    /// Without one, it's a `class_name` unless `init.meta.json` gives it a `className`.
+    fn load_folder(&mut self, parent: Ref, path: &Path, class_name: &str) -> Result<Ref, Error> {
+        let name = path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned();
+
+        let project_path = path.join(PROJECT_FILE);
+        if project_path.is_file() {
+            return self.load_project(parent, &project_path, &name);
+        }
+
+        let mut entries: Vec<(String, PathBuf)> = fs::read_dir(path)
+            .map_err(|error| Error::new(Operation::ReadFolder, path, error))?
+            .map(|entry| {
+                let entry =
+                    entry.map_err(|error| Error::new(Operation::ReadFolder, path, error))?;
+                Ok((
+                    entry.file_name().to_string_lossy().into_owned(),
+                    entry.path(),
+                ))
+            })
+            .collect::<Result<_, Error>>()?;
+        entries.sort();
+
+        let mut init = None;
+        for (file_name, entry_path) in &entries {
+            match classify(file_name) {
+                Some(("init", kind)) if entry_path.is_file() => {
+                    if is_folder_init(kind) && init.is_none() {
+                        init = Some((entry_path.clone(), kind));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let meta_path = path.join("init.meta.json");
+        let meta = self.read_meta(&meta_path)?;
+
+        let referent = match &init {
+            Some((init_path, kind)) => self.load_file(parent, init_path, *kind, &name)?,
+            None => {
+                let class_name = meta
+                    .as_ref()
+                    .and_then(|meta| meta.class_name.as_deref())
+                    .unwrap_or(class_name);
+                self.dom
+                    .insert(parent, InstanceBuilder::new(class_name).with_name(&name))
+            }
+        };
+
+        if let Some(meta) = &meta {
+            self.apply_meta(referent, meta, &meta_path);
+        }
+
+        for (file_name, entry_path) in &entries {
+            if entry_path.is_dir() {
+                self.load_folder(referent, entry_path, "Folder")?;
+                continue;
+            }
+
+            // The init file already is the folder's instance
+            if matches!(&init, Some((init_path, _)) if init_path == entry_path) {
+                continue;
+            }
+
+            let (stem, kind) = match classify(file_name) {
+                Some(classified) => classified,
+                None => continue,
+            };
+
+            let child = self.load_file(referent, entry_path, kind, stem)?;
+
+            // `init.meta.json` belongs to the folder, even next to a child named `init`
+            if stem == "init" {
+                continue;
+            }
+
+            let meta_path = path.join(format!("{}.meta.json", stem));
+            if let Some(meta) = self.read_meta(&meta_path)? {
+                self.apply_meta(child, &meta, &meta_path);
+            }
+        }
+
+        Ok(referent)
+    }
+
+    /// Builds the root of a project nested in the one being built, named after the project,
+    /// or `name` if it doesn't have one.
+    fn load_project(&mut self, parent: Ref, project_path: &Path, name: &str) -> Result<Ref, Error> {
+        let project: Value = read_json(project_path)?;
+        let tree = project
+            .get("tree")
+            .ok_or_else(|| invalid_file(project_path, "has no tree"))?;
+
+        let name = project.get("name").and_then(Value::as_str).unwrap_or(name);
+        let base = project_path.parent().unwrap_or_else(|| Path::new(""));
+        self.load_node(parent, name, tree, base)
+    }
+
+    /// Builds one node of a project and its children under `parent`. Paths are relative to
+    /// `base`, the folder of the project file.
+    fn load_node(
+        &mut self,
+        parent: Ref,
+        name: &str,
+        node: &Value,
+        base: &Path,
+    ) -> Result<Ref, Error> {
+        let class_name = node.get("$className").and_then(Value::as_str);
+
+        let referent = match node.get("$path").and_then(Value::as_str) {
+            Some(path) => {
+                let path = base.join(path);
+                let referent = if path.is_dir() {
+                    self.load_folder(parent, &path, class_name.unwrap_or("Folder"))?
+                } else {
+                    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+                    let (_, kind) = classify(&file_name)
+                        .ok_or_else(|| invalid_file(&path, "isn't a file Rojo can build from"))?;
+                    self.load_file(parent, &path, kind, name)?
+                };
+
+                self.dom
+                    .get_by_ref_mut(referent)
+                    .expect("got fake referent?")
+                    .name = name.to_string();
+                referent
+            }
+
+            None => self.dom.insert(
+                parent,
+                InstanceBuilder::new(class_name.unwrap_or("Folder")).with_name(name),
+            ),
+        };
+
+        let properties: Properties = node
+            .get("$properties")
+            .and_then(|properties| serde_json::from_value(properties.clone()).ok())
+            .unwrap_or_default();
+        let attributes: Properties = node
+            .get("$attributes")
+            .and_then(|attributes| serde_json::from_value(attributes.clone()).ok())
+            .unwrap_or_default();
+        self.apply_properties(referent, &properties, &attributes, Path::new(PROJECT_FILE));
+
+        if let Some(children) = node.as_object() {
+            for (child_name, child) in children {
+                if !child_name.starts_with('$') {
+                    self.load_node(referent, child_name, child, base)?;
+                }
+            }
+        }
+
+        Ok(referent)
+    }
+}
+
+/// Reads the Rojo project in `project_root` back into a tree, the way Rojo would build it:
+/// `default.project.json`, then the scripts, meta files, models and other files its paths
+/// point at. Only the explicit property syntax rbxlx-to-rojo writes is understood.
+pub fn build_project(project_root: &Path) -> Result<WeakDom, Error> {
+    let project_path = project_root.join(PROJECT_FILE);
+    let project: Value = read_json(&project_path)?;
+
+    let tree = project
+        .get("tree")
+        .ok_or_else(|| invalid_file(&project_path, "has no tree"))?;
+
+    let mut rebuilder = Rebuilder {
+        dom: WeakDom::new(InstanceBuilder::new("DataModel").with_name("DataModel")),
+    };
+
+    let root_ref = rebuilder.dom.root_ref();
+    let is_place = tree.get("$path").is_none()
+        && tree.get("$className").and_then(Value::as_str) == Some("DataModel");
+
+    if is_place {
+        if let Some(children) = tree.as_object() {
+            for (name, node) in children {
+                if !name.starts_with('$') {
+                    rebuilder.load_node(root_ref, name, node, project_root)?;
+                }
+            }
+        }
+    } else {
+        // A model project's tree is the model, named after the project
+        let name = project
+            .get("name")
+            .and_then(Value::as_str)
+            .unwrap_or("project");
+        rebuilder.load_node(root_ref, name, tree, project_root)?;
+    }
+
+    Ok(rebuilder.dom)
+}
+
+/// The `rojo` executable on the PATH, if there is one.
+pub fn find_rojo() -> Option<PathBuf> {
+    let file_name = if cfg!(windows) { "rojo.exe" } else { "rojo" };
+    env::split_paths(&env::var_os("PATH")?)
+        .map(|folder| folder.join(file_name))
+        .find(|path| path.is_file())
+}
+
+/// Builds the project in `project_root` with the Rojo executable at `rojo`, for an exact
+/// answer to what Rojo makes of it where `build_project` only follows its rules.
+pub fn build_project_with_rojo(project_root: &Path, rojo: &Path) -> Result<WeakDom, Error> {
+    let project_path = project_root.join(PROJECT_FILE);
+    let project: Value = read_json(&project_path)?;
+
+    // Rojo builds places into place files and anything else into model files
+    let is_place = project
+        .get("tree")
+        .and_then(|tree| tree.get("$className"))
+        .and_then(Value::as_str)
+        == Some("DataModel");
+    let output = std::env::temp_dir().join(format!(
+        "rbxlx-to-rojo-verify-{}.{}",
+        std::process::id(),
+        if is_place { "rbxlx" } else { "rbxmx" }
+    ));
+
+    let status = Command::new(rojo)
+        .arg("build")
+        .arg(project_root)
+        .arg("--output")
+        .arg(&output)
+        .status()
+        .map_err(|error| Error::new(Operation::BuildProject, project_root, error))?;
+
+    if !status.success() {
+        return Err(Error::new(
+            Operation::BuildProject,
+            project_root,
+            io::Error::new(io::ErrorKind::Other, format!("rojo build {}", status)),
+        ));
+    }
+
+    let contents = read_file(&output);
+    fs::remove_file(&output).ok();
+
+    let options = rbx_xml::DecodeOptions::new()
+        .property_behavior(rbx_xml::DecodePropertyBehavior::NoReflection);
+    rbx_xml::from_reader(contents?.as_slice(), options)
+        .map_err(|error| invalid_file(&output, error.to_string()))
+}
+
+/// How an instance of the rebuilt project differs from the original.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MismatchKind {
+    /// The original has it, the project doesn't.
+    Missing {
+        class_name: String,
+    },
+    /// The project has it, the original doesn't.
+    Unexpected {
+        class_name: String,
+    },
+    NameChanged {
+        expected: String,
+        actual: String,
+    },
+    ClassChanged {
+        expected: String,
+        actual: String,
+    },
+    /// A property, tag or attribute differs. Values are in Rojo's explicit syntax, and
+    /// `None` when the property isn't set to anything but its default.
+    PropertyChanged {
+        property: String,
+        expected: Option<String>,
+        actual: Option<String>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Mismatch {
+    /// The DataModel path of the instance, as it's named in the original.
+    pub instance: String,
+    pub kind: MismatchKind,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match &self.kind {
+            MismatchKind::Missing { class_name } => {
+                write!(formatter, "missing {} ({})", self.instance, class_name)
+            }
+
+            MismatchKind::Unexpected { class_name } => {
+                write!(formatter, "unexpected {} ({})", self.instance, class_name)
+            }
+
+            MismatchKind::NameChanged { expected, actual } => write!(
+                formatter,
+                "{} is named {:?} instead of {:?}",
+                self.instance, actual, expected
+            ),
+
+            MismatchKind::ClassChanged { expected, actual } => write!(
+                formatter,
+                "{} is a {} instead of a {}",
+                self.instance, actual, expected
+            ),
+
+            MismatchKind::PropertyChanged {
+                property,
+                expected,
+                actual,
+            } => write!(
+                formatter,
+                "{}.{} is {} instead of {}",
+                self.instance,
+                property,
+                actual.as_deref().unwrap_or("unset"),
+                expected.as_deref().unwrap_or("unset"),
+            ),
+        }
+    }
+}
+
+/// Everything that didn't survive a round trip through a project.
+#[derive(Clone, Debug, Default)]
+pub struct Verification {
+    pub mismatches: Vec<Mismatch>,
+}
+
+impl Verification {
+    pub fn is_empty(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+impl fmt::Display for Verification {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        for mismatch in &self.mismatches {
+            writeln!(formatter, "{}", mismatch)?;
+        }
+
+        write!(formatter, "{} mismatches", self.mismatches.len())
+    }
+}
+
+/// `Contents` is JSON that round trips through CSV, so it's compared by its entries.
+fn normalize_contents(instance: &Instance, properties: &mut Properties) {
+    if instance.class != "LocalizationTable" {
+        return;
+    }
+
+    let contents = match instance.properties.get(&ustr::ustr("Contents")) {
+        Some(Variant::String(contents)) => contents,
+        _ => return,
+    };
+
+    let entries: Vec<LocalizationEntry> = match serde_json::from_str(contents) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+
+    let non_empty = |field: &Option<String>| field.clone().filter(|field| !field.is_empty());
+    let entries: Vec<Value> = entries
+        .iter()
+        .map(|entry| {
+            json!({
+                "key": non_empty(&entry.key),
+                "source": non_empty(&entry.source),
+                "context": non_empty(&entry.context),
+                "example": non_empty(&entry.example),
+                "values": entry
+                    .values
+                    .iter()
+                    .filter(|(_, value)| !value.is_empty())
+                    .collect::<BTreeMap<_, _>>(),
+            })
+        })
+        .collect();
+
+    properties.insert("Contents".to_string(), Value::Array(entries));
+}
+
+fn source_of(instance: &Instance) -> Option<&str> {
+    match instance.properties.get(&ustr::ustr("Source")) {
+        Some(Variant::String(source)) => Some(source.as_str()),
+        _ => None,
+    }
+}
+
+struct Comparison<'a> {
+    expected: &'a WeakDom,
+    actual: &'a WeakDom,
+    /// Everything in `expected` the export deliberately left out.
+    skipped: &'a HashSet<Ref>,
+    mismatches: Vec<Mismatch>,
+}
+
+impl Comparison<'_> {
+    fn mismatch(&mut self, instance: String, kind: MismatchKind) {
+        self.mismatches.push(Mismatch { instance, kind });
+    }
+
+    fn compare_properties(&mut self, name: &str, expected: &Instance, actual: &Instance) {
+        let mut expected_properties = comparable_properties(expected);
+        let mut actual_properties = comparable_properties(actual);
+        normalize_contents(expected, &mut expected_properties);
+        normalize_contents(actual, &mut actual_properties);
+
+        if source_of(expected) != source_of(actual) {
+            self.mismatch(
+                name.to_string(),
+                MismatchKind::PropertyChanged {
+                    property: "Source".to_string(),
+                    expected: source_of(expected).map(|source| format!("{:?}", source)),
+                    actual: source_of(actual).map(|source| format!("{:?}", source)),
+                },
+            );
+        }
+
+        let names: BTreeSet<&String> = expected_properties
+            .keys()
+            .chain(actual_properties.keys())
+            .collect();
+
+        for property in names {
+            let expected_value = expected_properties.get(property);
+            let actual_value = actual_properties.get(property);
+
+            if expected_value != actual_value {
+                self.mismatch(
+                    name.to_string(),
+                    MismatchKind::PropertyChanged {
+                        property: property.clone(),
+                        expected: expected_value.map(Value::to_string),
+                        actual: actual_value.map(Value::to_string),
+                    },
+                );
+            }
+        }
+    }
+
+    fn compare(&mut self, expected: &Instance, actual: &Instance) {
+        let name = full_name(self.expected, expected);
+
+        if expected.name != actual.name {
+            self.mismatch(
+                name.clone(),
+                MismatchKind::NameChanged {
+                    expected: expected.name.clone(),
+                    actual: actual.name.clone(),
+                },
+            );
+        }
+
+        if expected.class != actual.class {
+            // Properties of different classes can't be compared meaningfully
+            self.mismatch(
+                name,
+                MismatchKind::ClassChanged {
+                    expected: expected.class.to_string(),
+                    actual: actual.class.to_string(),
+                },
+            );
+        } else {
+            self.compare_properties(&name, expected, actual);
+        }
+
+        self.compare_children(expected, actual);
+    }
+
+    fn compare_children(&mut self, expected: &Instance, actual: &Instance) {
+        let expected_children: Vec<&Instance> = expected
+            .children()
+            .iter()
+            .map(|child| {
+                self.expected
+                    .get_by_ref(*child)
+                    .expect("got fake child id?")
+            })
+            .filter(|child| !self.skipped.contains(&child.referent()))
+            .collect();
+
+        let mut actual_children: Vec<Option<&Instance>> = actual
+            .children()
+            .iter()
+            .map(|child| Some(self.actual.get_by_ref(*child).expect("got fake child id?")))
+            .collect();
+
+        let mut pairs = Vec::new();
+        let mut unmatched = Vec::new();
+
+        // Pair up by name and class first, then by name alone, then by class alone, which
+        // leaves a renamed instance paired with its original
+        type Matcher = fn(&Instance, &Instance) -> bool;
+        let matchers: [Matcher; 3] = [
+            |lhs, rhs| lhs.name == rhs.name && lhs.class == rhs.class,
+            |lhs, rhs| lhs.name == rhs.name,
+            |lhs, rhs| lhs.class == rhs.class,
+        ];
+
+        let mut remaining = expected_children;
+        for matcher in &matchers {
+            for expected_child in remaining.drain(..) {
+                let found = actual_children.iter_mut().find(|actual_child| {
+                    actual_child.map_or(false, |actual_child| matcher(expected_child, actual_child))
+                });
+
+                match found.and_then(Option::take) {
+                    Some(actual_child) => pairs.push((expected_child, actual_child)),
+                    None => unmatched.push(expected_child),
+                }
+            }
+
+            remaining = std::mem::take(&mut unmatched);
+        }
+
+        for (expected_child, actual_child) in pairs {
+            self.compare(expected_child, actual_child);
+        }
+
+        for expected_child in remaining {
+            self.mismatch(
+                full_name(self.expected, expected_child),
+                MismatchKind::Missing {
+                    class_name: expected_child.class.to_string(),
+                },
+            );
+        }
+
+        for actual_child in actual_children.into_iter().flatten() {
+            self.mismatch(
+                full_name(self.actual, actual_child),
+                MismatchKind::Unexpected {
+                    class_name: actual_child.class.to_string(),
+                },
+            );
+        }
+    }
+}
+
+/// Compares two trees instance by instance, starting below their roots. Instances of
+/// `expected` in `skipped` are expected to be missing, along with their descendants.
+pub fn compare_trees(expected: &WeakDom, actual: &WeakDom, skipped: &HashSet<Ref>) -> Verification {
+    let mut comparison = Comparison {
+        expected,
+        actual,
+        skipped,
+        mismatches: Vec::new(),
+    };
+
+    comparison.compare_children(expected.root(), actual.root());
+
+    debug!("found {} mismatches", comparison.mismatches.len());
+    Verification {
+        mismatches: comparison.mismatches,
+    }
+}
+
+/// Rebuilds the Rojo project in `project_root` and compares it with `tree`, the place it was
+/// exported from with `options`. Instances the export skips on purpose aren't expected in the
+/// project, so anything reported is something the conversion lost or changed.
+pub fn verify_project(
+    tree: &WeakDom,
+    project_root: &Path,
+    options: &ExportOptions,
+) -> Result<Verification, Error> {
+    let rebuilt = build_project(project_root)?;
+    verify_rebuilt(tree, &rebuilt, options)
+}
+
+/// Like `verify_project`, for a project that's already been built, e.g. with
+/// `build_project_with_rojo`.
+pub fn verify_rebuilt(
+    tree: &WeakDom,
+    rebuilt: &WeakDom,
+    options: &ExportOptions,
+) -> Result<Verification, Error> {
+    let mut dry_run = DryRun::new();
+    let report = process_instructions(tree, &mut dry_run, options)?;
+
+    let skipped = report
+        .with_decision(Decision::Skipped)
+        .map(|entry| entry.referent)
+        .collect();
+
+    Ok(compare_trees(tree, rebuilt, &skipped))
+}